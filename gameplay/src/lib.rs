@@ -0,0 +1,35 @@
+// Gameplay scripts the engine loads at runtime, see the engine's hot_reload.rs.
+//     cargo build -p gameplay
+//     GAMEPLAY_LIBRARY=target/debug/libgameplay.so cargo run --features hot_reload
+// Rebuilding the library while the engine runs swaps the scripts and keeps their state
+mod spinner;
+
+use log::warn;
+use probable_spork_r::{script::Script, script_registry};
+
+pub use spinner::Spinner;
+
+// Scripts are registered with #[derive(RegisterScript)], the same registry the engine uses
+#[no_mangle]
+pub fn create_script(name: &str) -> Option<Box<dyn Script>> {
+    match script_registry::create_script(name) {
+        Ok(script) => Some(script),
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
+    }
+}
+
+#[no_mangle]
+pub fn get_script_names() -> Vec<String> {
+    script_registry::get_script_names().into_iter().map(String::from).collect()
+}
+
+// The library has its own copy of the log statics, this forwards them to the engine's logger
+#[no_mangle]
+pub fn set_logger(logger: &'static dyn log::Log, level: log::LevelFilter) {
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
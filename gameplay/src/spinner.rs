@@ -0,0 +1,38 @@
+use cgmath::{Quaternion, Rotation3, Deg};
+use probable_spork_ecs::component::Entity;
+use probable_spork_r::{entities::components::Transform, script::{Script, ScriptComponentUpdater, ScriptContext}};
+use script_gen_macro::{ScriptComponentUpdater, Reflect, RegisterScript};
+
+// Turns the entity around its y axis
+#[derive(ScriptComponentUpdater, Reflect, RegisterScript)]
+pub struct Spinner {
+    entity: Entity,
+    // Keeps the transform of the entity it's added to
+    #[SyncComponent(existing)]
+    transform: Transform,
+    // Degrees per second
+    speed: f32
+}
+
+impl Default for Spinner {
+    fn default() -> Self {
+        Self {
+            entity: Entity::default(),
+            transform: Transform::default(),
+            speed: 90.0
+        }
+    }
+}
+
+impl Script for Spinner {
+    fn script_setup(&mut self, _context: &ScriptContext) {
+    }
+
+    fn script_update(&mut self, context: &ScriptContext) {
+        self.transform.rotation = self.transform.rotation * Quaternion::from_angle_y(Deg(self.speed * context.time.delta));
+    }
+
+    fn is_independent(&self) -> bool {
+        true
+    }
+}
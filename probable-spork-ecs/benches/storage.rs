@@ -0,0 +1,86 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use probable_spork_ecs::storage::{self, Storage, StorageType};
+
+const ENTITY_COUNTS: [u32; 2] = [10_000, 100_000];
+const STORAGE_TYPES: [StorageType; 2] = [StorageType::Dense, StorageType::SparseSet];
+
+#[derive(Clone, Copy)]
+struct Position {
+    x: f32,
+    y: f32,
+    z: f32
+}
+
+fn position(index: u32) -> Position {
+    Position {
+        x: index as f32,
+        y: 0.0,
+        z: 1.0
+    }
+}
+
+// Every `step`th entity gets a component
+fn filled_storage(storage_type: StorageType, entity_count: u32, step: usize) -> Box<dyn Storage<Position>> {
+    let mut storage = storage::new_storage(storage_type);
+    for index in (0..entity_count).step_by(step) {
+        storage.insert(index, position(index));
+    }
+    storage
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for entity_count in ENTITY_COUNTS {
+        for storage_type in STORAGE_TYPES {
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", storage_type), entity_count), &entity_count, |b, entity_count| {
+                b.iter(|| filled_storage(storage_type, *entity_count, 1));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+    for entity_count in ENTITY_COUNTS {
+        for storage_type in STORAGE_TYPES {
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", storage_type), entity_count), &entity_count, |b, entity_count| {
+                b.iter_batched_ref(
+                    || filled_storage(storage_type, *entity_count, 1),
+                    |storage| for index in 0..*entity_count {
+                        black_box(storage.remove(index));
+                    },
+                    BatchSize::LargeInput
+                );
+            });
+        }
+    }
+    group.finish();
+}
+
+fn iterate(c: &mut Criterion, name: &str, step: usize) {
+    let mut group = c.benchmark_group(name);
+    for entity_count in ENTITY_COUNTS {
+        for storage_type in STORAGE_TYPES {
+            let storage = filled_storage(storage_type, entity_count, step);
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", storage_type), entity_count), &storage, |b, storage| {
+                b.iter(|| storage.iter().fold(0.0, |sum, (_, position)| sum + position.x + position.y + position.z));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn iterate_all(c: &mut Criterion) {
+    iterate(c, "iterate_all", 1);
+}
+
+// One entity in ten has the component, the dense layout still walks every slot
+fn iterate_sparse(c: &mut Criterion) {
+    iterate(c, "iterate_sparse", 10);
+}
+
+criterion_group!(benches, insert, remove, iterate_all, iterate_sparse);
+criterion_main!(benches);
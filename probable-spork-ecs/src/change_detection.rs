@@ -0,0 +1,86 @@
+use std::{ops::{Deref, DerefMut}, sync::atomic::{AtomicU32, Ordering}};
+
+use atomic_refcell::AtomicRefMut;
+
+// Change ticks of when a component was added and last written. ComponentStorage increments its
+// tick once per system run, a system sees changes made after the tick of its own previous run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: tick
+        }
+    }
+
+    pub fn is_added(&self, last_run: u32) -> bool {
+        self.added > last_run
+    }
+
+    pub fn is_changed(&self, last_run: u32) -> bool {
+        self.changed > last_run
+    }
+}
+
+// ComponentTicks that can be written through a shared borrow, see ComponentStorage::get_entity_component_mut
+pub(crate) struct AtomicTicks {
+    added: AtomicU32,
+    changed: AtomicU32
+}
+
+impl AtomicTicks {
+    pub fn new(tick: u32) -> Self {
+        Self {
+            added: AtomicU32::new(tick),
+            changed: AtomicU32::new(tick)
+        }
+    }
+
+    pub fn get(&self) -> ComponentTicks {
+        ComponentTicks {
+            added: self.added.load(Ordering::Relaxed),
+            changed: self.changed.load(Ordering::Relaxed)
+        }
+    }
+
+    pub fn set_changed(&self, tick: u32) {
+        self.changed.store(tick, Ordering::Relaxed);
+    }
+}
+
+// Mutable component borrow, marks the component as changed when it's written through
+pub struct Mut<'w, T> {
+    value: AtomicRefMut<'w, T>,
+    ticks: &'w AtomicTicks,
+    change_tick: u32
+}
+
+impl<'w, T> Mut<'w, T> {
+    pub(crate) fn new(value: AtomicRefMut<'w, T>, ticks: &'w AtomicTicks, change_tick: u32) -> Self {
+        Self {
+            value,
+            ticks,
+            change_tick
+        }
+    }
+}
+
+impl<'w, T> Deref for Mut<'w, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<'w, T> DerefMut for Mut<'w, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.ticks.set_changed(self.change_tick);
+        &mut self.value
+    }
+}
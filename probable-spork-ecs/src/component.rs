@@ -0,0 +1,508 @@
+use std::{any::{Any, TypeId}, collections::HashMap, error::Error, fmt::Display, str::FromStr, sync::atomic::{AtomicU32, Ordering}};
+
+use atomic_refcell::{AtomicRef, AtomicRefCell};
+use log::warn;
+
+use crate::{storage::{self, Storage, StorageType}, change_detection::{AtomicTicks, ComponentTicks, Mut}};
+
+// Index plus the generation of the slot it was created in. Despawning bumps the slot's generation,
+// so handles to a despawned entity stop matching even after the index gets reused
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entity {
+    index: u32,
+    generation: u32
+}
+
+impl Entity {
+    pub fn new(index: u32, generation: u32) -> Self {
+        Self {
+            index,
+            generation
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl Display for Entity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseEntityError(String);
+
+impl Error for ParseEntityError {}
+impl Display for ParseEntityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected an entity like 3v1, got {}", self.0)
+    }
+}
+
+// Reads the Display format back
+impl FromStr for Entity {
+    type Err = ParseEntityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s.split_once('v')
+            .and_then(|(index, generation)| Some(Entity::new(index.parse().ok()?, generation.parse().ok()?)));
+        parsed.ok_or_else(|| ParseEntityError(s.to_string()))
+    }
+}
+
+// Send + Sync so systems can run on other threads. Two systems borrowing the same component
+// mutably at once still panic, like a RefCell would
+pub trait Component: Send + Sync {
+    // Sparse sets suit components only some entities have, Dense the ones nearly all of them have
+    const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+
+    fn setup(&mut self, world: &ComponentStorage);
+    fn update(&mut self, world: &ComponentStorage);
+    // Runs before the component is removed or its entity despawned, while the rest of the entity is still there
+    fn on_destroy(&mut self, _world: &ComponentStorage) {
+    }
+}
+
+// Type erased side of a component column
+trait AnyColumn: Send + Sync {
+    fn setup(&self, world: &ComponentStorage);
+    fn update(&self, world: &ComponentStorage);
+    fn on_destroy(&self, index: u32, world: &ComponentStorage);
+    fn remove(&mut self, index: u32);
+    fn len(&self) -> usize;
+    fn indices(&self) -> Vec<u32>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// A component and its change ticks, removing the component drops its ticks too
+struct ComponentCell<T> {
+    component: AtomicRefCell<T>,
+    ticks: AtomicTicks
+}
+
+impl<T> ComponentCell<T> {
+    fn new(component: T, tick: u32) -> Self {
+        Self {
+            component: AtomicRefCell::new(component),
+            ticks: AtomicTicks::new(tick)
+        }
+    }
+}
+
+struct Column<T> {
+    storage: Box<dyn Storage<ComponentCell<T>> + Send + Sync>
+}
+
+impl<T: Component + 'static> AnyColumn for Column<T> {
+    fn setup(&self, world: &ComponentStorage) {
+        self.storage.iter().for_each(|(_, cell)| cell.component.borrow_mut().setup(world));
+    }
+
+    fn update(&self, world: &ComponentStorage) {
+        self.storage.iter().for_each(|(_, cell)| cell.component.borrow_mut().update(world));
+    }
+
+    fn on_destroy(&self, index: u32, world: &ComponentStorage) {
+        if let Some(cell) = self.storage.get(index) {
+            cell.component.borrow_mut().on_destroy(world);
+        }
+    }
+
+    fn remove(&mut self, index: u32) {
+        self.storage.remove(index);
+    }
+
+    fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        self.storage.iter().map(|(index, _)| index).collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+struct EntitySlot {
+    generation: u32,
+    is_alive: bool
+}
+
+pub struct ComponentStorage {
+    slots: Vec<EntitySlot>,
+    // Live entities in creation order
+    entities: Vec<Entity>,
+    // Indices of despawned entities, reused by create_entity
+    free_indices: Vec<u32>,
+    // Kept in registration order so components update in a stable order
+    columns: Vec<Box<dyn AnyColumn>>,
+    column_indices: HashMap<TypeId, usize>,
+    change_tick: AtomicU32
+}
+
+impl ComponentStorage {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            entities: vec![],
+            free_indices: vec![],
+            columns: vec![],
+            column_indices: HashMap::new(),
+            // Systems start with a last run tick of 0, so they see everything added before their first run
+            change_tick: AtomicU32::new(1)
+        }
+    }
+
+    // Writes and additions get marked with the current tick
+    pub fn get_change_tick(&self) -> u32 {
+        self.change_tick.load(Ordering::Relaxed)
+    }
+
+    // Called before every system run, returns the tick the run writes with
+    pub fn increment_change_tick(&self) -> u32 {
+        self.change_tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn setup_components(&self) {
+        self.columns.iter().for_each(|column| column.setup(self));
+    }
+
+    pub fn update_components(&self) {
+        self.columns.iter().for_each(|column| column.update(self));
+    }
+
+    // Generations start at 1, so Entity::default() never matches a live entity
+    pub fn create_entity(&mut self) -> Entity {
+        let entity = match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.is_alive = true;
+                Entity::new(index, slot.generation)
+            },
+            None => {
+                self.slots.push(EntitySlot {
+                    generation: 1,
+                    is_alive: true
+                });
+                Entity::new(self.slots.len() as u32 - 1, 1)
+            }
+        };
+        self.entities.push(entity);
+        entity
+    }
+
+    pub fn is_alive(&self, entity: &Entity) -> bool {
+        self.slots.get(entity.index as usize).is_some_and(|slot| slot.is_alive && slot.generation == entity.generation)
+    }
+
+    // The live entity using the index, if any
+    pub fn get_entity(&self, index: u32) -> Option<Entity> {
+        match self.slots.get(index as usize) {
+            Some(slot) if slot.is_alive => Some(Entity::new(index, slot.generation)),
+            _ => None
+        }
+    }
+
+    pub fn get_entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    // Every component gets on_destroy before any of them is removed. Returns false for stale handles
+    pub fn despawn(&mut self, entity: &Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        self.columns.iter().for_each(|column| column.on_destroy(entity.index, self));
+        self.columns.iter_mut().for_each(|column| column.remove(entity.index));
+
+        if let Some(position) = self.entities.iter().position(|other| other == entity) {
+            self.entities.remove(position);
+        }
+        let slot = &mut self.slots[entity.index as usize];
+        slot.is_alive = false;
+        slot.generation = slot.generation.wrapping_add(1).max(1);
+        self.free_indices.push(entity.index);
+        true
+    }
+
+    pub fn remove_component<T: Component + 'static>(&mut self, entity: &Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        if let Some(cell) = self.get_storage::<T>()?.get(entity.index) {
+            cell.component.borrow_mut().on_destroy(self);
+        }
+        self.get_storage_mut::<T>().remove(entity.index).map(|cell| cell.component.into_inner())
+    }
+
+    fn get_storage<T: Component + 'static>(&self) -> Option<&(dyn Storage<ComponentCell<T>> + Send + Sync)> {
+        let column_index = self.column_indices.get(&TypeId::of::<T>())?;
+        self.columns[*column_index].as_any()
+            .downcast_ref::<Column<T>>()
+            .map(|column| column.storage.as_ref())
+    }
+
+    fn get_storage_mut<T: Component + 'static>(&mut self) -> &mut (dyn Storage<ComponentCell<T>> + Send + Sync) {
+        let column_index = *self.column_indices.entry(TypeId::of::<T>()).or_insert_with(|| {
+            self.columns.push(Box::new(Column::<T> {
+                storage: storage::new_storage(T::STORAGE_TYPE)
+            }));
+            self.columns.len() - 1
+        });
+
+        self.columns[column_index].as_any_mut()
+            .downcast_mut::<Column<T>>()
+            .map(|column| column.storage.as_mut())
+            .expect("Component column stored under the wrong type")
+    }
+
+    // Replaces the entity's previous component of the same type, which counts as a change, not an addition
+    pub fn register_component<T: Component + 'static>(&mut self, entity: &Entity, component: T) {
+        if !self.is_alive(entity) {
+            warn!("Can't add {} to despawned entity {}", std::any::type_name::<T>(), entity);
+            return;
+        }
+
+        let tick = self.get_change_tick();
+        let storage = self.get_storage_mut::<T>();
+        match storage.get_mut(entity.index) {
+            Some(cell) => {
+                *cell.component.get_mut() = component;
+                cell.ticks.set_changed(tick);
+            },
+            None => {
+                storage.insert(entity.index, ComponentCell::new(component, tick));
+            }
+        }
+    }
+
+    // None for stale handles, even when the index is used by a newer entity
+    pub fn get_entity_component<T: Component + 'static>(&self, entity: &Entity) -> Option<AtomicRef<'_, T>> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.get_storage::<T>()?.get(entity.index).map(|cell| cell.component.borrow())
+    }
+
+    pub fn get_entity_component_mut<T: Component + 'static>(&self, entity: &Entity) -> Option<Mut<'_, T>> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.get_storage::<T>()?.get(entity.index)
+            .map(|cell| Mut::new(cell.component.borrow_mut(), &cell.ticks, self.get_change_tick()))
+    }
+
+    pub fn get_component_ticks<T: Component + 'static>(&self, entity: &Entity) -> Option<ComponentTicks> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.get_storage::<T>()?.get(entity.index).map(|cell| cell.ticks.get())
+    }
+
+    // Only visits entities that have the component, in storage order
+    pub fn iter<T: Component + 'static>(&self) -> impl Iterator<Item = (Entity, AtomicRef<'_, T>)> + '_ {
+        self.get_storage::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.iter())
+            .filter_map(|(index, cell)| self.get_entity(index).map(|entity| (entity, cell.component.borrow())))
+    }
+
+    pub fn count<T: Component + 'static>(&self) -> usize {
+        self.get_storage::<T>().map_or(0, |storage| storage.len())
+    }
+
+    // Untyped count, for callers that only have the TypeId
+    pub fn count_by_type_id(&self, type_id: TypeId) -> usize {
+        self.column_indices.get(&type_id).map_or(0, |column_index| self.columns[*column_index].len())
+    }
+
+    // Entities that have the component, in storage order
+    pub fn get_entities_by_type_id(&self, type_id: TypeId) -> Vec<Entity> {
+        match self.column_indices.get(&type_id) {
+            Some(column_index) => self.columns[*column_index].indices().into_iter()
+                .filter_map(|index| self.get_entity(index))
+                .collect(),
+            None => vec![]
+        }
+    }
+}
+
+impl Default for ComponentStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    struct Health(u32);
+
+    impl Component for Health {
+        fn setup(&mut self, _world: &ComponentStorage) {
+        }
+        fn update(&mut self, _world: &ComponentStorage) {
+        }
+    }
+
+    struct DestroyCounter(Arc<AtomicU32>);
+
+    impl Component for DestroyCounter {
+        fn setup(&mut self, _world: &ComponentStorage) {
+        }
+        fn update(&mut self, _world: &ComponentStorage) {
+        }
+        fn on_destroy(&mut self, _world: &ComponentStorage) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn stale_handle_misses_recycled_index() {
+        let mut world = ComponentStorage::new();
+        let old = world.create_entity();
+        world.register_component(&old, Health(10));
+        assert!(world.despawn(&old));
+
+        let new = world.create_entity();
+        world.register_component(&new, Health(20));
+
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new, old);
+        assert!(world.get_entity_component::<Health>(&old).is_none());
+        assert_eq!(world.get_entity_component::<Health>(&new).map(|health| health.0), Some(20));
+        assert!(!world.despawn(&old));
+    }
+
+    #[test]
+    fn entities_keep_creation_order() {
+        let mut world = ComponentStorage::new();
+        let entities: Vec<Entity> = (0..4).map(|_| world.create_entity()).collect();
+        world.despawn(&entities[1]);
+        let recycled = world.create_entity();
+
+        assert_eq!(world.get_entities(), &[entities[0], entities[2], entities[3], recycled]);
+    }
+
+    #[test]
+    fn type_id_lookup_only_sees_owners() {
+        let mut world = ComponentStorage::new();
+        let entities: Vec<Entity> = (0..3).map(|_| world.create_entity()).collect();
+        world.register_component(&entities[0], Health(10));
+        world.register_component(&entities[2], Health(20));
+        world.despawn(&entities[0]);
+
+        let type_id = TypeId::of::<Health>();
+        assert_eq!(world.count_by_type_id(type_id), 1);
+        assert_eq!(world.get_entities_by_type_id(type_id), vec![entities[2]]);
+        assert!(world.get_entities_by_type_id(TypeId::of::<DestroyCounter>()).is_empty());
+    }
+
+    #[test]
+    fn entity_parses_its_display_format() {
+        let entity = Entity::new(7, 3);
+        assert_eq!(entity.to_string().parse::<Entity>().ok(), Some(entity));
+        assert!("7".parse::<Entity>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn entity_serde_round_trip() {
+        let entity = Entity::new(7, 3);
+        let json = serde_json::to_string(&entity).expect("Entity serializes");
+        assert_eq!(serde_json::from_str::<Entity>(&json).ok(), Some(entity));
+    }
+
+    #[test]
+    fn changes_are_seen_once_per_reader() {
+        let mut world = ComponentStorage::new();
+        let entity = world.create_entity();
+        world.register_component(&entity, Health(10));
+
+        // One reader runs before the writer and one after it, every frame
+        let mut last_runs = [0, 0];
+        let mut seen = [0, 0];
+        let mut read = |world: &ComponentStorage, reader: usize| {
+            let this_run = world.increment_change_tick();
+            if world.get_component_ticks::<Health>(&entity).unwrap().is_changed(last_runs[reader]) {
+                seen[reader] += 1;
+            }
+            last_runs[reader] = this_run;
+        };
+
+        // The first frame sees the addition, the second one the write
+        for frame in 0..4 {
+            read(&world, 0);
+            world.increment_change_tick();
+            if frame == 1 {
+                world.get_entity_component_mut::<Health>(&entity).unwrap().0 = 5;
+            }
+            read(&world, 1);
+        }
+        assert_eq!(seen, [2, 2]);
+    }
+
+    #[test]
+    fn reading_doesnt_mark_changed() {
+        let mut world = ComponentStorage::new();
+        let entity = world.create_entity();
+        world.register_component(&entity, Health(10));
+        let last_run = world.increment_change_tick();
+
+        let health = world.get_entity_component_mut::<Health>(&entity).unwrap();
+        assert_eq!(health.0, 10);
+        drop(health);
+        assert!(!world.get_component_ticks::<Health>(&entity).unwrap().is_changed(last_run));
+    }
+
+    #[test]
+    fn storage_is_sync() {
+        fn assert_sync<T: Send + Sync>() {
+        }
+        assert_sync::<ComponentStorage>();
+    }
+
+    #[test]
+    fn default_entity_is_never_alive() {
+        let mut world = ComponentStorage::new();
+        world.create_entity();
+        assert!(!world.is_alive(&Entity::default()));
+    }
+
+    #[test]
+    fn despawn_and_remove_run_on_destroy() {
+        let destroyed = Arc::new(AtomicU32::new(0));
+        let mut world = ComponentStorage::new();
+        let first = world.create_entity();
+        let second = world.create_entity();
+        world.register_component(&first, DestroyCounter(destroyed.clone()));
+        world.register_component(&second, DestroyCounter(destroyed.clone()));
+
+        assert!(world.remove_component::<DestroyCounter>(&first).is_some());
+        assert!(world.get_entity_component::<DestroyCounter>(&first).is_none());
+        world.despawn(&second);
+
+        assert_eq!(destroyed.load(Ordering::Relaxed), 2);
+        assert_eq!(world.count::<DestroyCounter>(), 0);
+    }
+}
@@ -0,0 +1,9 @@
+pub mod component;
+pub mod storage;
+pub mod change_detection;
+
+// Borrow tracking of components and resources, like RefCell but Sync
+pub use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
+
+// For code generated by script_gen_macro, so scripts don't need their own log dependency
+pub use log;
@@ -0,0 +1,63 @@
+use super::Storage;
+
+// One slot per entity index, iterating walks the empty slots too
+pub struct DenseStorage<T> {
+    slots: Vec<Option<T>>,
+    len: usize
+}
+
+impl<T> DenseStorage<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            len: 0
+        }
+    }
+}
+
+impl<T> Default for DenseStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Storage<T> for DenseStorage<T> {
+    fn insert(&mut self, index: u32, component: T) -> Option<T> {
+        let index = index as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+
+        let previous = self.slots[index].replace(component);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    fn remove(&mut self, index: u32) -> Option<T> {
+        let removed = self.slots.get_mut(index as usize)?.take();
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        self.slots.get(index as usize)?.as_ref()
+    }
+
+    fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        self.slots.get_mut(index as usize)?.as_mut()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (u32, &T)> + '_> {
+        Box::new(self.slots.iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_ref().map(|component| (index as u32, component))))
+    }
+}
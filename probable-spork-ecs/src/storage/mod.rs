@@ -0,0 +1,35 @@
+mod dense;
+mod sparse_set;
+
+pub use dense::DenseStorage;
+pub use sparse_set::SparseSet;
+
+// Layout of one component type, picked with Component::STORAGE_TYPE
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StorageType {
+    // One slot per entity, for components nearly every entity has
+    Dense,
+    // Packed components plus an entity index lookup, for components only some entities have
+    SparseSet
+}
+
+// Components of one type, addressed by entity index
+pub trait Storage<T> {
+    // Returns the replaced component
+    fn insert(&mut self, index: u32, component: T) -> Option<T>;
+    fn remove(&mut self, index: u32) -> Option<T>;
+    fn get(&self, index: u32) -> Option<&T>;
+    fn get_mut(&mut self, index: u32) -> Option<&mut T>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = (u32, &T)> + '_>;
+}
+
+pub fn new_storage<T: Send + Sync + 'static>(storage_type: StorageType) -> Box<dyn Storage<T> + Send + Sync> {
+    match storage_type {
+        StorageType::Dense => Box::new(DenseStorage::new()),
+        StorageType::SparseSet => Box::new(SparseSet::new())
+    }
+}
@@ -0,0 +1,79 @@
+use super::Storage;
+
+const EMPTY: u32 = u32::MAX;
+
+// Components are packed together, the sparse vec maps an entity index to its position in them.
+// Removing swaps the last component into the hole, so the order isn't stable
+pub struct SparseSet<T> {
+    sparse: Vec<u32>,
+    dense: Vec<T>,
+    // Entity index of every packed component
+    indices: Vec<u32>
+}
+
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        Self {
+            sparse: vec![],
+            dense: vec![],
+            indices: vec![]
+        }
+    }
+
+    fn get_position(&self, index: u32) -> Option<usize> {
+        match self.sparse.get(index as usize) {
+            Some(position) if *position != EMPTY => Some(*position as usize),
+            _ => None
+        }
+    }
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Storage<T> for SparseSet<T> {
+    fn insert(&mut self, index: u32, component: T) -> Option<T> {
+        if let Some(position) = self.get_position(index) {
+            return Some(std::mem::replace(&mut self.dense[position], component));
+        }
+
+        if index as usize >= self.sparse.len() {
+            self.sparse.resize(index as usize + 1, EMPTY);
+        }
+        self.sparse[index as usize] = self.dense.len() as u32;
+        self.dense.push(component);
+        self.indices.push(index);
+        None
+    }
+
+    fn remove(&mut self, index: u32) -> Option<T> {
+        let position = self.get_position(index)?;
+        self.sparse[index as usize] = EMPTY;
+
+        let removed = self.dense.swap_remove(position);
+        self.indices.swap_remove(position);
+        if let Some(moved_index) = self.indices.get(position) {
+            self.sparse[*moved_index as usize] = position as u32;
+        }
+        Some(removed)
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        self.get_position(index).map(|position| &self.dense[position])
+    }
+
+    fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        self.get_position(index).map(|position| &mut self.dense[position])
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (u32, &T)> + '_> {
+        Box::new(self.indices.iter().copied().zip(self.dense.iter()))
+    }
+}
@@ -1,7 +1,11 @@
 extern crate proc_macro;
 mod script_component_updater;
+mod reflect;
+mod register_script;
 
 use script_component_updater::ScriptComponentUpdaterMacro;
+use reflect::ReflectMacro;
+use register_script::RegisterScriptMacro;
 use syn::{self, parse_macro_input, DeriveInput};
 use proc_macro::TokenStream;
 
@@ -12,3 +16,19 @@ pub fn derive_macro(input: TokenStream) -> TokenStream {
     let output = ScriptComponentUpdaterMacro::generate_output(ast);
     TokenStream::from(output)
 }
+
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    let output = ReflectMacro::generate_output(ast);
+    TokenStream::from(output)
+}
+
+#[proc_macro_derive(RegisterScript)]
+pub fn derive_register_script(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    let output = RegisterScriptMacro::generate_output(ast);
+    TokenStream::from(output)
+}
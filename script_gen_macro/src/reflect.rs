@@ -0,0 +1,96 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{DeriveInput, Field};
+
+pub struct ReflectMacro;
+
+impl ReflectMacro {
+    // #[reflect(skip)] hides a field
+    fn is_skipped(field: &Field) -> syn::Result<bool> {
+        let mut is_skipped = false;
+        for attribute in field.attrs.iter().filter(|attribute| attribute.path().is_ident("reflect")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    is_skipped = true;
+                    return Ok(());
+                }
+                Err(meta.error("unsupported reflect option, expected `skip`"))
+            })?;
+        }
+        Ok(is_skipped)
+    }
+
+    // Paths are absolute so the derive works in gameplay crates, the engine aliases itself as probable_spork_r
+    pub fn generate_output(ast: DeriveInput) -> TokenStream {
+        match Self::try_generate_output(ast) {
+            Ok(output) => output,
+            Err(e) => e.to_compile_error()
+        }
+    }
+
+    fn try_generate_output(ast: DeriveInput) -> syn::Result<TokenStream> {
+        let struct_name = ast.ident.clone();
+        let fields = match &ast.data {
+            syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&ast.ident, "Reflect can only be derived for structs with named fields"))
+        };
+
+        let mut reflected_fields = vec![];
+        for field in fields.iter() {
+            if !Self::is_skipped(field)? {
+                reflected_fields.push(field);
+            }
+        }
+
+        let field_infos = reflected_fields.iter().map(|field| {
+            let (ident, ty) = (&field.ident, &field.ty);
+            quote! {
+                ::probable_spork_r::reflect::FieldInfo { name: stringify!(#ident), type_name: std::any::type_name::<#ty>() }
+            }
+        });
+
+        let get_arms = reflected_fields.iter().map(|field| {
+            let ident = &field.ident;
+            quote! {
+                stringify!(#ident) => Some(::probable_spork_r::reflect::ReflectField::to_reflect_value(&self.#ident)),
+            }
+        });
+
+        let set_arms = reflected_fields.iter().map(|field| {
+            let ident = &field.ident;
+            quote! {
+                stringify!(#ident) => match ::probable_spork_r::reflect::ReflectField::set_from_reflect_value(&mut self.#ident, value) {
+                    true => Ok(()),
+                    false => Err(::probable_spork_r::reflect::ReflectError::TypeMismatch(name.to_string()))
+                },
+            }
+        });
+
+        Ok(quote! {
+            impl ::probable_spork_r::reflect::Reflect for #struct_name {
+                fn get_type_name(&self) -> &'static str {
+                    stringify!(#struct_name)
+                }
+
+                fn get_fields(&self) -> Vec<::probable_spork_r::reflect::FieldInfo> {
+                    vec![#(#field_infos),*]
+                }
+
+                fn get_field(&self, name: &str) -> Option<::probable_spork_r::reflect::ReflectValue> {
+                    match name {
+                        #(#get_arms)*
+                        _ => None
+                    }
+                }
+
+                #[allow(unused_variables)]
+                fn set_field(&mut self, name: &str, value: ::probable_spork_r::reflect::ReflectValue) -> Result<(), ::probable_spork_r::reflect::ReflectError> {
+                    match name {
+                        #(#set_arms)*
+                        _ => Err(::probable_spork_r::reflect::ReflectError::FieldNotFound(name.to_string()))
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,32 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+pub struct RegisterScriptMacro;
+
+impl RegisterScriptMacro {
+    // Registers the type under its name, the script is created from its Default impl.
+    // Paths are absolute like in the Reflect derive, so gameplay crates can register scripts too
+    pub fn generate_output(ast: DeriveInput) -> TokenStream {
+        let struct_name = ast.ident;
+        if !ast.generics.params.is_empty() {
+            return syn::Error::new_spanned(&ast.generics, "RegisterScript can't be derived for generic scripts").to_compile_error();
+        }
+
+        quote! {
+            const _: () = {
+                fn create() -> Box<dyn ::probable_spork_r::script::Script> {
+                    Box::new(<#struct_name as Default>::default())
+                }
+
+                ::probable_spork_r::script_registry::inventory::submit! {
+                    ::probable_spork_r::script_registry::ScriptRegistration {
+                        name: stringify!(#struct_name),
+                        type_path: concat!(module_path!(), "::", stringify!(#struct_name)),
+                        create
+                    }
+                }
+            };
+        }
+    }
+}
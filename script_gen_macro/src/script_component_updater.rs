@@ -1,26 +1,181 @@
 use proc_macro2::{TokenStream, Ident};
 use quote::quote;
-use syn::{DeriveInput, Field, Type};
+use syn::{DeriveInput, Field, Type, PathArguments, GenericArgument, punctuated::Punctuated, token::Comma};
+
+// A #[SyncComponent] field and its options
+struct SyncField {
+    ident: Ident,
+    // Component type, the T of an optional Option<T> field
+    ty: Type,
+    // Option<T> field, None while the entity doesn't have the component
+    optional: bool,
+    // Never written back to the entity
+    read_only: bool,
+    // Taken from the entity on setup instead of overwriting it
+    existing: bool
+}
 
 pub struct ScriptComponentUpdaterMacro;
 
 impl ScriptComponentUpdaterMacro {
-    fn script_fn_closure<'a, F: Fn(Type, Ident) -> TokenStream + 'a>(filtered_fields: &'a Vec<&'a Field>, closure: F) -> impl Iterator<Item = TokenStream> + 'a {
-        let register_calls = filtered_fields
-            .iter()
-            .map(move |field| -> TokenStream {
-                let cl_field = (**field).clone();
-                let variable = cl_field.ident.unwrap();
-                let ty = cl_field.ty;
-
-
-                (closure)(ty, variable)
-            });
-        return register_calls;
+    fn get_option_inner(ty: &Type) -> Option<&Type> {
+        let segment = match ty {
+            Type::Path(type_path) if type_path.qself.is_none() => type_path.path.segments.last()?,
+            _ => return None
+        };
+        if segment.ident != "Option" {
+            return None;
+        }
+        match &segment.arguments {
+            PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => match arguments.args.first()? {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn is_entity_type(ty: &Type) -> bool {
+        match ty {
+            Type::Path(type_path) => type_path.path.segments.last().is_some_and(|segment| segment.ident == "Entity"),
+            _ => false
+        }
+    }
+
+    // The generated code stores the entity it syncs with in `entity`
+    fn check_entity_field(struct_name: &Ident, fields: &Punctuated<Field, Comma>) -> syn::Result<()> {
+        match fields.iter().find(|field| field.ident.as_ref().is_some_and(|ident| ident == "entity")) {
+            Some(field) if Self::is_entity_type(&field.ty) => Ok(()),
+            Some(field) => Err(syn::Error::new_spanned(&field.ty, "the `entity` field must be a probable_spork_ecs::component::Entity")),
+            None => Err(syn::Error::new_spanned(struct_name, "ScriptComponentUpdater needs an `entity: Entity` field"))
+        }
+    }
+
+    fn parse_sync_field(field: &Field) -> syn::Result<Option<SyncField>> {
+        let attribute = match field.attrs.iter().find(|attribute| attribute.path().is_ident("SyncComponent")) {
+            Some(attribute) => attribute,
+            None => return Ok(None)
+        };
+
+        let (mut optional, mut read_only, mut existing) = (false, false, false);
+        if !matches!(attribute.meta, syn::Meta::Path(_)) {
+            attribute.parse_nested_meta(|meta| {
+                match meta.path.get_ident().map(|ident| ident.to_string()).as_deref() {
+                    Some("optional") => optional = true,
+                    Some("read_only") => read_only = true,
+                    Some("existing") => existing = true,
+                    _ => return Err(meta.error("unsupported SyncComponent option, expected `optional`, `read_only` or `existing`"))
+                }
+                Ok(())
+            })?;
+        }
+
+        let ident = match &field.ident {
+            Some(ident) => ident.clone(),
+            None => return Err(syn::Error::new_spanned(field, "SyncComponent fields need a name"))
+        };
+
+        let ty = match (optional, Self::get_option_inner(&field.ty)) {
+            (true, Some(inner)) => inner.clone(),
+            (true, None) => return Err(syn::Error::new_spanned(&field.ty, "#[SyncComponent(optional)] fields must be an Option<T>")),
+            (false, Some(_)) => return Err(syn::Error::new_spanned(&field.ty, "Option fields need #[SyncComponent(optional)]")),
+            (false, None) => field.ty.clone()
+        };
+
+        // References, tuples, arrays and the like can't be components
+        if !matches!(ty, Type::Path(_)) {
+            return Err(syn::Error::new_spanned(&ty, "SyncComponent fields must be a component type"));
+        }
+
+        Ok(Some(SyncField {
+            ident,
+            ty,
+            optional,
+            read_only,
+            existing
+        }))
+    }
+
+    fn generate_pre_setup(field: &SyncField) -> TokenStream {
+        let SyncField { ident, ty, .. } = field;
+        match (field.optional, field.existing) {
+            (false, false) => quote! {
+                world.register_component::<#ty>(&self.entity, self.#ident.clone());
+            },
+            (false, true) => quote! {
+                match world.get_entity_component::<#ty>(&self.entity) {
+                    Some(c) => self.#ident = c.clone(),
+                    None => probable_spork_ecs::log::warn!("Entity {} is missing {}, required by {}", self.entity, stringify!(#ty), stringify!(#ident))
+                }
+            },
+            // A Some value only gets added when the entity doesn't have the component yet
+            (true, false) => quote! {
+                let existing = world.get_entity_component::<#ty>(&self.entity).map(|c| c.clone());
+                match existing {
+                    Some(c) => self.#ident = Some(c),
+                    None => if let Some(c) = &self.#ident {
+                        world.register_component::<#ty>(&self.entity, c.clone());
+                    }
+                }
+            },
+            (true, true) => quote! {
+                self.#ident = world.get_entity_component::<#ty>(&self.entity).map(|c| c.clone());
+            }
+        }
+    }
+
+    fn generate_pre_user_update(field: &SyncField) -> TokenStream {
+        let SyncField { ident, ty, .. } = field;
+        match field.optional {
+            false => quote! {
+                if let Some(c) = world.get_entity_component::<#ty>(&self.entity) {
+                    if self.#ident != *c {
+                        self.#ident = c.clone()
+                    }
+                }
+            },
+            true => quote! {
+                let c = world.get_entity_component::<#ty>(&self.entity).map(|c| c.clone());
+                if self.#ident != c {
+                    self.#ident = c;
+                }
+            }
+        }
+    }
+
+    fn generate_post_user_update(field: &SyncField) -> TokenStream {
+        let SyncField { ident, ty, .. } = field;
+        match (field.read_only, field.optional) {
+            (true, _) => quote! {},
+            (false, false) => quote! {
+                if let Some(mut c) = world.get_entity_component_mut::<#ty>(&self.entity) {
+                    if *c != self.#ident {
+                        *c = self.#ident.clone();
+                    }
+                }
+            },
+            // Components can't be added or removed here, only an existing one gets the new value
+            (false, true) => quote! {
+                if let (Some(value), Some(mut c)) = (&self.#ident, world.get_entity_component_mut::<#ty>(&self.entity)) {
+                    if *c != *value {
+                        *c = value.clone();
+                    }
+                }
+            }
+        }
     }
 
     pub fn generate_output(ast: DeriveInput) -> TokenStream {
+        match Self::try_generate_output(ast) {
+            Ok(output) => output,
+            Err(e) => e.to_compile_error()
+        }
+    }
+
+    fn try_generate_output(ast: DeriveInput) -> syn::Result<TokenStream> {
         let struct_name = ast.ident.clone();
+        let generics = ast.generics.clone();
+        let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
         let attribute_fields = match ast {
             syn::DeriveInput {
                 data: syn::Data::Struct(
@@ -34,52 +189,44 @@ impl ScriptComponentUpdaterMacro {
                     }
                 ), ..
             } => fields,
-            _ => unimplemented!("Couldn't find any fields on struct")
+            _ => return Err(syn::Error::new_spanned(&struct_name, "ScriptComponentUpdater can only be derived for structs with named fields"))
         };
 
-        let filtered_fields: Vec<&syn::Field> = attribute_fields
-            .iter()
-            .filter(|field| {
-                for attribute in field.attrs.iter() {
-                    if attribute.path().get_ident().unwrap() == "SyncComponent" {
-                        return true;
-                    }
-                }
-                false
-            })
-            .collect();
+        // Every problem gets reported, not just the first one
+        let mut sync_fields = vec![];
+        let mut errors: Option<syn::Error> = None;
+        let mut push_error = |e: syn::Error| match errors.as_mut() {
+            Some(errors) => errors.combine(e),
+            None => errors = Some(e)
+        };
 
-        let register_calls = ScriptComponentUpdaterMacro::script_fn_closure(&filtered_fields, |ty, variable| {
-            quote! {
-                world.register_component::<#ty>(&self.entity, self.#variable.clone());
+        if let Err(e) = Self::check_entity_field(&struct_name, &attribute_fields) {
+            push_error(e);
+        }
+        for field in attribute_fields.iter() {
+            match Self::parse_sync_field(field) {
+                Ok(Some(sync_field)) => sync_fields.push(sync_field),
+                Ok(None) => {},
+                Err(e) => push_error(e)
             }
-        });
+        }
+        if let Some(errors) = errors {
+            return Err(errors);
+        }
 
-        let pre_user_update_calls = ScriptComponentUpdaterMacro::script_fn_closure(&filtered_fields, |ty, variable| {
-            quote! {
-                if let Some(c) = world.get_entity_component::<#ty>(&self.entity) {
-                    if self.#variable != *c {
-                        self.#variable = c.clone()
-                    }
-                }
-            }
-        });
+        let pre_setup_calls = sync_fields.iter().map(Self::generate_pre_setup);
+        let pre_user_update_calls = sync_fields.iter().map(Self::generate_pre_user_update);
+        let post_user_update_calls = sync_fields.iter().map(Self::generate_post_user_update);
 
-        let post_user_update_calls = ScriptComponentUpdaterMacro::script_fn_closure(&filtered_fields, |ty, variable| {
-            quote! {
-                if let Some(mut c) = world.get_entity_component_mut::<#ty>(&self.entity) {
-                    if *c != self.#variable {
-                        *c = self.#variable.clone();
-                    }
+        Ok(quote! {
+            impl #impl_generics ScriptComponentUpdater for #struct_name #type_generics #where_clause {
+                fn get_entity(&self) -> probable_spork_ecs::component::Entity {
+                    self.entity.clone()
                 }
-            }
-        });
 
-
-        quote! {
-            impl ScriptComponentUpdater for #struct_name {
                 fn pre_setup(&mut self, entity: probable_spork_ecs::component::Entity, world: &mut probable_spork_ecs::component::ComponentStorage) {
-                    #(#register_calls)*
+                    self.entity = entity;
+                    #(#pre_setup_calls)*
                 }
 
                 fn pre_user_update(&mut self, world: &probable_spork_ecs::component::ComponentStorage) {
@@ -89,6 +236,6 @@ impl ScriptComponentUpdaterMacro {
                     #(#post_user_update_calls)*
                 }
             }
-        }
+        })
     }
 }
@@ -0,0 +1,7 @@
+// Run with TRYBUILD=overwrite to regenerate the .stderr files after changing a diagnostic
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+}
@@ -0,0 +1,21 @@
+// Stand-ins for the engine types the pass fixtures derive against, included with #[path]
+use probable_spork_ecs::component::{Component, ComponentStorage, Entity};
+
+pub trait ScriptComponentUpdater {
+    fn get_entity(&self) -> Entity;
+    fn pre_setup(&mut self, entity: Entity, world: &mut ComponentStorage);
+    fn pre_user_update(&mut self, world: &ComponentStorage);
+    fn post_user_update(&mut self, world: &ComponentStorage);
+}
+
+#[derive(Clone, PartialEq, Default)]
+pub struct Transform {
+    x: f32
+}
+
+impl Component for Transform {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
@@ -0,0 +1,14 @@
+use script_gen_macro::ScriptComponentUpdater;
+
+#[derive(ScriptComponentUpdater)]
+pub struct NoEntityScript {
+    speed: f32
+}
+
+#[derive(ScriptComponentUpdater)]
+pub struct WrongEntityScript {
+    entity: u32
+}
+
+fn main() {
+}
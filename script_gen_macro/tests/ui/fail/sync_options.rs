@@ -0,0 +1,21 @@
+use probable_spork_ecs::component::Entity;
+use script_gen_macro::ScriptComponentUpdater;
+
+#[derive(Clone, PartialEq)]
+pub struct Transform;
+
+#[derive(ScriptComponentUpdater)]
+pub struct BadOptionsScript<'a> {
+    entity: Entity,
+    #[SyncComponent(sometimes)]
+    unknown_option: Transform,
+    #[SyncComponent(optional)]
+    not_an_option: Transform,
+    #[SyncComponent]
+    missing_optional: Option<Transform>,
+    #[SyncComponent]
+    reference: &'a Transform
+}
+
+fn main() {
+}
@@ -0,0 +1,13 @@
+use probable_spork_ecs::component::Entity;
+use script_gen_macro::ScriptComponentUpdater;
+
+#[derive(ScriptComponentUpdater)]
+pub struct TupleScript(Entity);
+
+#[derive(ScriptComponentUpdater)]
+pub enum EnumScript {
+    Script
+}
+
+fn main() {
+}
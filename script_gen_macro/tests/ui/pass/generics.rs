@@ -0,0 +1,26 @@
+use std::marker::PhantomData;
+
+use probable_spork_ecs::component::{Component, Entity};
+use script_gen_macro::ScriptComponentUpdater;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::{ScriptComponentUpdater, Transform};
+
+// Synced component types can come from the script's generics
+#[derive(ScriptComponentUpdater)]
+pub struct GenericScript<'a, T> where T: Component + Clone + PartialEq + 'static {
+    entity: Entity,
+    #[SyncComponent]
+    component: T,
+    #[SyncComponent(optional)]
+    transform: Option<Transform>,
+    name: PhantomData<&'a str>
+}
+
+fn assert_updater<T: ScriptComponentUpdater>() {
+}
+
+fn main() {
+    assert_updater::<GenericScript<'static, Transform>>();
+}
@@ -0,0 +1,24 @@
+use script_gen_macro::ScriptComponentUpdater;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::{ScriptComponentUpdater, Transform};
+
+#[derive(ScriptComponentUpdater)]
+pub struct OptionsScript {
+    entity: probable_spork_ecs::component::Entity,
+    #[SyncComponent(optional)]
+    optional: Option<Transform>,
+    #[SyncComponent(read_only)]
+    read_only: Transform,
+    #[SyncComponent(existing)]
+    existing: Transform,
+    #[SyncComponent(optional, existing, read_only)]
+    all: Option<Transform>,
+    // Attributes with a path are ignored
+    #[rustfmt::skip]
+    speed: f32
+}
+
+fn main() {
+}
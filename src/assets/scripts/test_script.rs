@@ -1,17 +1,16 @@
 
 use cgmath::{Vector3, Quaternion, Rotation3, Deg};
-use log::info;
 use probable_spork_ecs::component::Entity;
-use script_gen_macro::ScriptComponentUpdater;
+use script_gen_macro::{ScriptComponentUpdater, Reflect, RegisterScript};
 
 use crate::entities::components::MeshInstance;
-use crate::{entities::components::Transform, script::Script};
+use crate::{entities::components::Transform, script::{Script, ScriptContext}};
 use crate::script::ScriptComponentUpdater;
 
 
 // Get rid of this crates entities
 // Move Script trait to appropriate place
-#[derive(ScriptComponentUpdater, Default)]
+#[derive(ScriptComponentUpdater, Reflect, RegisterScript, Default)]
 pub struct TestScript {
     entity: Entity,
     #[SyncComponent]
@@ -21,12 +20,35 @@ pub struct TestScript {
 }
 
 impl Script for TestScript {
-    fn script_setup(&mut self) {
+    fn script_setup(&mut self, _context: &ScriptContext) {
         self.mesh.local_transform.rotation = Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), Deg(-45.0));
         self.transform.position.x += 2.5;
         self.transform.rotation = Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), Deg(-45.0));
     }
 
-    fn script_update(&mut self) {
+    fn script_update(&mut self, _context: &ScriptContext) {
+    }
+
+    fn is_independent(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{scene::Scene, time::Time};
+    use super::*;
+
+    #[test]
+    fn setup_writes_synced_components_back() {
+        let mut scene = Scene::new();
+        scene.insert_resource(Time::new(0.02));
+        let entity = scene.create_entity();
+        scene.add_boxed_script_to_entity(&entity, Box::new(TestScript::default()));
+        assert!(scene.component_storage.get_entity_component::<MeshInstance>(&entity).is_some());
+
+        scene.setup_script(&entity);
+        let position = scene.component_storage.get_entity_component::<Transform>(&entity).map(|transform| transform.position.x);
+        assert_eq!(position, Some(2.5));
     }
 }
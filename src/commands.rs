@@ -0,0 +1,184 @@
+use probable_spork_ecs::component::{Component, Entity};
+
+use crate::{scene::Scene, script::{self, Script}};
+
+type EntityCommand = Box<dyn FnOnce(&mut Scene, &Entity) + Send>;
+type SceneCommand = Box<dyn FnOnce(&mut Scene) + Send>;
+
+// Components of an entity spawned through Commands
+#[derive(Default)]
+pub struct EntityBuilder {
+    components: Vec<EntityCommand>,
+    // Added after the components, so the script can sync with them
+    script: Option<Box<dyn Script>>
+}
+
+impl EntityBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<T: Component + 'static>(mut self, component: T) -> Self {
+        match script::into_script(component) {
+            Ok(script) => self.script = Some(script),
+            Err(component) => self.components.push(Box::new(move |scene, entity| scene.add_component_to_entity(entity, component)))
+        }
+        self
+    }
+}
+
+// Commands are applied after Scene::setup_components, so scripts are set up when they're added
+fn add_script(scene: &mut Scene, entity: &Entity, script: Box<dyn Script>) {
+    scene.add_boxed_script_to_entity(entity, script);
+    scene.setup_script(entity);
+}
+
+enum Command {
+    Spawn(EntityBuilder),
+    Despawn(Entity),
+    Insert(Entity, EntityCommand),
+    Scene(SceneCommand)
+}
+
+// Structural changes recorded while the scene is borrowed, applied in order by Scene::apply_commands
+#[derive(Default)]
+pub struct Commands {
+    queue: Vec<Command>
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, builder: EntityBuilder) {
+        self.queue.push(Command::Spawn(builder));
+    }
+
+    pub fn despawn(&mut self, entity: &Entity) {
+        self.queue.push(Command::Despawn(*entity));
+    }
+
+    pub fn insert<T: Component + 'static>(&mut self, entity: &Entity, component: T) {
+        let command: EntityCommand = match script::into_script(component) {
+            Ok(script) => Box::new(move |scene, entity| add_script(scene, entity, script)),
+            Err(component) => Box::new(move |scene, entity| scene.add_component_to_entity(entity, component))
+        };
+        self.queue.push(Command::Insert(*entity, command));
+    }
+
+    pub fn remove<T: Component + 'static>(&mut self, entity: &Entity) {
+        self.queue.push(Command::Insert(*entity, Box::new(|scene, entity| {
+            scene.remove_component::<T>(entity);
+        })));
+    }
+
+    // Adds a registered script by name and runs its setup
+    pub fn add_script(&mut self, entity: &Entity, name: &str) {
+        let name = name.to_string();
+        self.queue.push(Command::Insert(*entity, Box::new(move |scene, entity| {
+            if scene.add_script_by_name(entity, &name) {
+                scene.setup_script(entity);
+            }
+        })));
+    }
+
+    pub fn send_event<T: Send + 'static>(&mut self, event: T) {
+        self.queue.push(Command::Scene(Box::new(move |scene| scene.send_event(event))));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn apply(self, scene: &mut Scene) {
+        for command in self.queue {
+            match command {
+                Command::Spawn(builder) => {
+                    let entity = scene.create_entity();
+                    for add_component in builder.components {
+                        add_component(scene, &entity);
+                    }
+                    if let Some(script) = builder.script {
+                        add_script(scene, &entity, script);
+                    }
+                },
+                Command::Despawn(entity) => scene.despawn(&entity),
+                Command::Insert(entity, add_component) => add_component(scene, &entity),
+                Command::Scene(command) => command(scene)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, atomic::{AtomicU32, Ordering}};
+
+    use probable_spork_ecs::component::ComponentStorage;
+    use script_gen_macro::Reflect;
+
+    use crate::{script::{ScriptComponentUpdater, ScriptContext}, entities::components::ScriptEnabled, time::Time};
+    use super::*;
+
+    #[derive(Reflect)]
+    struct CountingScript {
+        entity: Entity,
+        #[reflect(skip)]
+        setups: Arc<AtomicU32>
+    }
+
+    impl ScriptComponentUpdater for CountingScript {
+        fn get_entity(&self) -> Entity {
+            self.entity
+        }
+        fn pre_setup(&mut self, entity: Entity, _world: &mut ComponentStorage) {
+            self.entity = entity;
+        }
+        fn pre_user_update(&mut self, _world: &ComponentStorage) {}
+        fn post_user_update(&mut self, _world: &ComponentStorage) {}
+    }
+
+    impl Script for CountingScript {
+        fn script_setup(&mut self, _context: &ScriptContext) {
+            self.setups.fetch_add(1, Ordering::Relaxed);
+        }
+        fn script_update(&mut self, _context: &ScriptContext) {}
+    }
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.insert_resource(Time::new(0.02));
+        scene
+    }
+
+    fn script(setups: &Arc<AtomicU32>) -> Box<dyn Script> {
+        Box::new(CountingScript { entity: Entity::default(), setups: setups.clone() })
+    }
+
+    #[test]
+    fn spawned_script_is_set_up() {
+        let mut scene = scene();
+        let setups = Arc::new(AtomicU32::new(0));
+        scene.get_commands_mut().spawn(EntityBuilder::new().with(script(&setups)));
+        scene.apply_commands();
+
+        let entity = scene.component_storage.get_entities()[0];
+        let script_entity = scene.component_storage.get_entity_component::<Box<dyn Script>>(&entity).map(|script| script.get_entity());
+        assert_eq!(script_entity, Some(entity));
+        assert!(scene.component_storage.get_entity_component::<ScriptEnabled>(&entity).is_some());
+        assert_eq!(setups.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn inserted_script_is_set_up() {
+        let mut scene = scene();
+        let setups = Arc::new(AtomicU32::new(0));
+        let entity = scene.create_entity();
+        scene.get_commands_mut().insert(&entity, script(&setups));
+        scene.apply_commands();
+
+        assert!(scene.component_storage.get_entity_component::<ScriptEnabled>(&entity).is_some());
+        assert_eq!(setups.load(Ordering::Relaxed), 1);
+    }
+}
@@ -1,22 +1,22 @@
-use std::fmt::format;
-
-use egui::{Separator, PaintCallbackInfo, Ui, ClippedPrimitive, TexturesDelta, Label, Sense, Rect, Style, Widget};
+use egui::{Separator, PaintCallbackInfo, Ui, ClippedPrimitive, TexturesDelta, Sense, Style, Widget};
 use egui_winit::EventResponse;
-use log::info;
+use log::{info, warn};
+use probable_spork_ecs::component::{Component, Entity};
 use winit::{event_loop::EventLoop, event::WindowEvent};
 
-use crate::{RendererResources, renderer::RendererLoop, entities::components::MeshRenderer, scene::Scene};
+use crate::{RendererResources, renderer::{RendererLoop, CullingStats, MeshManager}, scene::Scene, entities::Camera, time::Time};
+use crate::{entities::components::{Transform, MeshInstance, ScriptEnabled}, reflect::{Reflect, ReflectValue}, script::Script, commands::EntityBuilder, script_registry};
 
 type UpdateCallback = dyn Fn(
         &wgpu::Device,
         &wgpu::Queue,
         &mut wgpu::CommandEncoder,
         &RendererResources,
-        &Vec<Box<dyn MeshRenderer>>
+        &MeshManager
     ) + Send + Sync;
 
 type PaintCallback =
-    dyn for<'a, 'b> Fn(PaintCallbackInfo, &'a mut wgpu::RenderPass<'b>, &'b RendererResources, &'b Vec<Box<dyn MeshRenderer>>) + Send + Sync;
+    dyn for<'a, 'b> Fn(PaintCallbackInfo, &'a mut wgpu::RenderPass<'b>, &'b RendererResources, &'b MeshManager) + Send + Sync;
 
 pub struct GamePreviewCallback {
     pub update: Box<UpdateCallback>,
@@ -27,6 +27,9 @@ pub struct Editor{
     pub ctx: egui::Context,
     pub pixels_per_point: f32,
     winit_state: egui_winit::State,
+    // Entity being renamed in the scene panel and the name typed so far
+    renaming: Option<(Entity, String)>,
+    selected: Option<Entity>
 }
 
 impl Editor {
@@ -48,7 +51,9 @@ impl Editor {
         Self {
             ctx,
             pixels_per_point,
-            winit_state
+            winit_state,
+            renaming: None,
+            selected: None
         }
     }
 
@@ -56,13 +61,13 @@ impl Editor {
         self.winit_state.on_event(&self.ctx, event)
     }
 
-    fn setup_game_preview_callback<'a>(&self, ui: &mut Ui) {
+    fn setup_game_preview_callback(ui: &mut Ui) {
         let available_size = ui.available_size();
         let (rect, _response) = ui.allocate_at_least(available_size, egui::Sense::drag());
 
         let cb = GamePreviewCallback {
-            update: Box::new(|_device, queue, _encoder, renderer_resources, meshes| RendererLoop::update(queue, renderer_resources, meshes)),
-            render: Box::new(|_info, rpass, renderer_resources, meshes| RendererLoop::render(rpass, renderer_resources, meshes))
+            update: Box::new(|_device, queue, encoder, renderer_resources, mesh_manager| RendererLoop::update(queue, encoder, renderer_resources, mesh_manager)),
+            render: Box::new(|_info, rpass, renderer_resources, mesh_manager| RendererLoop::render(rpass, renderer_resources, mesh_manager))
         };
 
         let callback = egui::PaintCallback {
@@ -74,23 +79,166 @@ impl Editor {
     }
 
 
-    pub fn draw(&mut self, window: &winit::window::Window, _renderer_resources: &RendererResources, scene: &Scene) -> (TexturesDelta, Vec<ClippedPrimitive>) {
+    fn draw_culling_stats(ui: &mut Ui, culling_stats: &CullingStats) {
+        egui::CollapsingHeader::new("Culling")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.label(format!("Meshes: {}", culling_stats.meshes));
+                if culling_stats.is_gpu_culled {
+                    ui.label(format!("Instances: {} (culled on GPU)", culling_stats.instances));
+                    return;
+                }
+                ui.label(format!("Visible instances: {}/{}", culling_stats.visible_instances, culling_stats.instances));
+                ui.label(format!("Culled instances: {}", culling_stats.get_culled_instances()));
+            });
+    }
+
+    fn draw_camera(ui: &mut Ui, scene: &Scene) {
+        egui::CollapsingHeader::new("Camera")
+            .default_open(true)
+            .show(ui, |ui| match scene.resource::<Camera>() {
+                Some(camera) => {
+                    ui.label(format!("Position: {:.2}, {:.2}, {:.2}", camera.eye.x, camera.eye.y, camera.eye.z));
+                    ui.label(format!("Target: {:.2}, {:.2}, {:.2}", camera.target.x, camera.target.y, camera.target.z));
+                },
+                None => {
+                    ui.label("No camera in the scene");
+                }
+            });
+    }
+
+    fn draw_time(ui: &mut Ui, scene: &Scene) {
+        egui::CollapsingHeader::new("Time")
+            .default_open(false)
+            .show(ui, |ui| match scene.resource_mut::<Time>() {
+                Some(mut time) => {
+                    ui.label(format!("Frame: {}, {:.2} ms", time.frame_count, time.unscaled_delta * 1000.0));
+                    ui.label(format!("Elapsed: {:.2} s", time.elapsed));
+                    ui.horizontal(|ui| {
+                        ui.label("Time scale");
+                        ui.add(egui::DragValue::new(&mut time.time_scale).speed(0.05).clamp_range(0.0..=10.0));
+                    });
+                },
+                None => {
+                    ui.label("No time resource in the scene");
+                }
+            });
+    }
+
+    fn draw_events(ui: &mut Ui, scene: &Scene) {
+        egui::CollapsingHeader::new("Events")
+            .default_open(false)
+            .show(ui, |ui| {
+                if scene.get_event_channels().is_empty() {
+                    ui.label("No event types registered");
+                }
+                for channel in scene.get_event_channels().iter() {
+                    let pending = channel.get_pending(scene);
+                    egui::CollapsingHeader::new(format!("{} ({})", channel.type_name, pending.len()))
+                        .show(ui, |ui| pending.iter().for_each(|event| {
+                            ui.label(event);
+                        }));
+                }
+            });
+    }
+
+    // Draws every widget before checking, any() alone would stop drawing at the first edited one
+    fn any_changed(changes: impl Iterator<Item = bool>) -> bool {
+        changes.collect::<Vec<bool>>().into_iter().any(|changed| changed)
+    }
+
+    // Returns true when the value was edited
+    fn draw_reflect_value(ui: &mut Ui, label: &str, value: &mut ReflectValue) -> bool {
+        let drag = |ui: &mut Ui, value: &mut f32| ui.add(egui::DragValue::new(value).speed(0.1)).changed();
+
+        match value {
+            ReflectValue::Struct(fields) => egui::CollapsingHeader::new(label)
+                .default_open(true)
+                .show(ui, |ui| Self::any_changed(fields.iter_mut().map(|(name, value)| Self::draw_reflect_value(ui, name, value))))
+                .body_returned
+                .unwrap_or(false),
+            _ => ui.horizontal(|ui| {
+                ui.label(label);
+                match value {
+                    ReflectValue::Bool(value) => ui.checkbox(value, "").changed(),
+                    ReflectValue::U32(value) => ui.add(egui::DragValue::new(value)).changed(),
+                    ReflectValue::Usize(value) => ui.add(egui::DragValue::new(value)).changed(),
+                    ReflectValue::F32(value) => drag(ui, value),
+                    ReflectValue::String(value) => ui.text_edit_singleline(value).changed(),
+                    ReflectValue::Vec3(values) => Self::any_changed(values.iter_mut().map(|value| drag(ui, value))),
+                    ReflectValue::Quat(values) => Self::any_changed(values.iter_mut().map(|value| drag(ui, value))),
+                    // Handles can't be edited, a typo would point at another entity
+                    ReflectValue::Entity(index, generation) => {
+                        ui.label(format!("{}v{}", index, generation));
+                        false
+                    },
+                    ReflectValue::Struct(_) => false
+                }
+            }).inner
+        }
+    }
+
+    fn draw_component<T: Component + Reflect + 'static>(ui: &mut Ui, scene: &Scene, entity: &Entity) {
+        if let Some(mut component) = scene.get_component_mut::<T>(entity) {
+            let mut value = component.to_value();
+            // Only writes (and marks the component changed) when something was edited
+            if Self::draw_reflect_value(ui, component.get_type_name(), &mut value) {
+                if let Err(e) = component.apply_value(value) {
+                    warn!("Couldn't apply edited {}: {}", std::any::type_name::<T>(), e);
+                }
+            }
+        }
+    }
+
+    fn draw_properties(ui: &mut Ui, scene: &Scene, selected: &Option<Entity>) {
+        let entity = match selected {
+            Some(entity) if scene.is_alive(entity) => entity,
+            _ => {
+                ui.label("No entity selected");
+                return;
+            }
+        };
+
+        Self::draw_component::<Transform>(ui, scene, entity);
+        Self::draw_component::<MeshInstance>(ui, scene, entity);
+
+        let script_enabled = scene.component_storage.get_entity_component::<ScriptEnabled>(entity).map(|enabled| enabled.0);
+        if let Some(mut enabled) = script_enabled {
+            if ui.checkbox(&mut enabled, "Script enabled").changed() {
+                scene.set_script_enabled(entity, enabled);
+            }
+        }
+
+        if let Some(mut script) = scene.get_component_mut::<Box<dyn Script>>(entity) {
+            let (type_name, mut value) = (script.get_type_name(), script.to_value());
+            if Self::draw_reflect_value(ui, type_name, &mut value) {
+                if let Err(e) = script.apply_value(value) {
+                    warn!("Couldn't apply edited script {}: {}", type_name, e);
+                }
+            }
+        }
+    }
+
+    pub fn draw(&mut self, window: &winit::window::Window, renderer_resources: &RendererResources, scene: &Scene) -> (TexturesDelta, Vec<ClippedPrimitive>) {
         let raw_input = self.winit_state.take_egui_input(window);
+        let Self { renaming, selected, .. } = self;
         let full_output = self.ctx.run(raw_input, |ctx| {
             egui::SidePanel::left("Scene panel").show(ctx, |ui| {
                 ui.heading("Scene");
                 ui.add(Separator::default().horizontal());
                 egui::Frame::menu(&Style::default())
                     .fill(egui::Color32::BLACK)
-                    .show(ui, |ui| ui.add(EntityList::new(scene.component_storage.entities)))
+                    .show(ui, |ui| ui.add(EntityList::new(scene, renaming, selected)))
             });
             egui::SidePanel::right("Right panel").show(ctx, |ui| {
                 ui.heading("Properties");
                 ui.add(Separator::default().horizontal());
-                if ui.button("Click me").clicked() {
-                    // take some action here
-                    info!("Pressed hello world button");
-                }
+                Self::draw_properties(ui, scene, selected);
+                ui.add(Separator::default().horizontal());
+                Self::draw_culling_stats(ui, &renderer_resources.culling_stats);
+                Self::draw_camera(ui, scene);
+                Self::draw_time(ui, scene);
+                Self::draw_events(ui, scene);
             });
             egui::TopBottomPanel::bottom("Content browser").show(ctx, |ui| {
                 ui.heading("Content browser");
@@ -106,7 +254,7 @@ impl Editor {
                     let available_size = ui.available_size();
                     ui.set_min_height(available_size.y * 0.3);
                     ui.set_min_width(available_size.x * 0.6);
-                    self.setup_game_preview_callback(ui);
+                    Self::setup_game_preview_callback(ui);
                 });
             });
         });
@@ -117,37 +265,89 @@ impl Editor {
 
 }
 
-struct EntityList {
-    entities: u32
+struct EntityList<'a> {
+    scene: &'a Scene,
+    renaming: &'a mut Option<(Entity, String)>,
+    selected: &'a mut Option<Entity>
 }
 
-impl EntityList {
-    fn new(entities: u32) -> Self {
+impl<'a> EntityList<'a> {
+    fn new(scene: &'a Scene, renaming: &'a mut Option<(Entity, String)>, selected: &'a mut Option<Entity>) -> Self {
         Self {
-            entities
+            scene,
+            renaming,
+            selected
         }
     }
 
-    fn scene_context_menu(ui: &mut Ui) {
-        let _ = ui.button("Add empty");
+    // Structural changes go through the command buffer and show up next frame
+    fn scene_context_menu(ui: &mut Ui, scene: &Scene) {
+        if ui.button("Add empty").clicked() {
+            scene.get_commands_mut().spawn(EntityBuilder::new());
+            ui.close_menu();
+        }
     }
 
-    fn entity_context_menu(ui: &mut Ui) {
-        let _ = ui.button("Rename");
-        let _ = ui.button("Remove");
+    fn entity_context_menu(ui: &mut Ui, scene: &Scene, entity: Entity, name: String, renaming: &mut Option<(Entity, String)>) {
+        if ui.button("Rename").clicked() {
+            *renaming = Some((entity, name));
+            ui.close_menu();
+        }
+        // An entity holds one script at most
+        let has_script = scene.component_storage.get_entity_component::<Box<dyn Script>>(&entity).is_some();
+        if !has_script {
+            let mut names: Vec<String> = script_registry::get_script_names().into_iter().map(String::from).collect();
+            #[cfg(feature = "hot_reload")]
+            if let Some(Ok(library_names)) = scene.resource::<crate::hot_reload::GameplayLibrary>().map(|library| library.get_script_names()) {
+                let library_names: Vec<String> = library_names.into_iter().filter(|name| !names.contains(name)).collect();
+                names.extend(library_names);
+            }
+            ui.menu_button("Add script", |ui| for name in names.iter() {
+                if ui.button(name).clicked() {
+                    scene.get_commands_mut().add_script(&entity, name);
+                    ui.close_menu();
+                }
+            });
+        }
+        if ui.button("Remove").clicked() {
+            scene.get_commands_mut().despawn(&entity);
+            ui.close_menu();
+        }
     }
 }
 
-impl Widget for EntityList{
+impl<'a> Widget for EntityList<'a> {
     fn ui(self, ui: &mut Ui) -> egui::Response {
+        let EntityList { scene, renaming, selected } = self;
+
         ui.vertical(|ui| {
-            for entity in 0..self.entities {
-                let ent_response = ui.add(Label::new(format!("Entity {}", entity)).sense(Sense::click()));
-                ent_response.context_menu(EntityList::entity_context_menu);
+            for (entity, name) in scene.get_entity_names() {
+                let is_renaming = renaming.as_ref().is_some_and(|(renamed_entity, _)| *renamed_entity == entity);
+
+                if !is_renaming {
+                    let is_selected = selected.as_ref().is_some_and(|selected_entity| *selected_entity == entity);
+                    let ent_response = ui.selectable_label(is_selected, name.clone());
+                    if ent_response.clicked() {
+                        *selected = Some(entity);
+                    }
+                    ent_response.context_menu(|ui| EntityList::entity_context_menu(ui, scene, entity, name, renaming));
+                    continue;
+                }
+
+                if let Some((_, new_name)) = renaming.as_mut() {
+                    // Enter or clicking elsewhere applies the name
+                    let name_response = ui.text_edit_singleline(new_name);
+                    if name_response.lost_focus() {
+                        scene.rename_entity(&entity, new_name.clone());
+                        *renaming = None;
+                    } else {
+                        name_response.request_focus();
+                    }
+                }
             }
         });
 
         let response = ui.allocate_response(egui::vec2(ui.available_width(), ui.available_height()), Sense::click());
-        response.context_menu(EntityList::scene_context_menu)
+        response.context_menu(|ui| EntityList::scene_context_menu(ui, scene))
     }
 }
@@ -1,27 +1,101 @@
 use log::{warn, info};
 use winit::event::WindowEvent;
 
-use crate::{entities::{CameraUniform, CameraController, Camera, components::{MeshInstance, MeshRenderer, Transform}}, RendererResources, scene::Scene, assets::TestScript, renderer::Renderer};
+use crate::{entities::{CameraController, Camera, components::{MeshInstance, Transform, LodGroup, ScriptEnabled, Parent}}, RendererResources, scene::Scene, assets::TestScript, renderer::{self, Renderer, Frustum, ExtractedCamera, ExtractedMeshInstances, ExtractedLodGroups}};
+use crate::schedule::{Schedule, System, Stage, SystemTicks};
+use crate::prefab::Prefab;
+use crate::time::Time;
+use crate::input::Input;
+use crate::scripting::{self, ScriptingEngine, ScriptedBehaviour};
+
+// Seconds between fixed updates
+const FIXED_DELTA: f32 = 0.02;
+// Units per second
+const CAMERA_SPEED: f32 = 12.0;
 
 pub struct Engine {
-    camera_controller: CameraController,
-    camera: Camera,
+    schedule: Schedule,
     pub scene: Scene
 }
 
 impl Engine {
     pub fn new(config: &wgpu::SurfaceConfiguration) -> Self {
-        let camera = Self::init_camera(&config);
-        let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera);
+        let camera = Self::init_camera(config);
+        let camera_controller = CameraController::new(CAMERA_SPEED);
+
+        let mut scene = Scene::new();
+        scene.insert_resource(camera);
+        scene.insert_resource(camera_controller);
+        scene.insert_resource(Time::new(FIXED_DELTA));
+        scene.insert_resource(Input::new());
+        scene.insert_resource(ScriptingEngine::new());
+        renderer::insert_extract_resources(&mut scene);
+        #[cfg(feature = "hot_reload")]
+        if let Some(path) = std::env::var_os("GAMEPLAY_LIBRARY") {
+            scene.insert_resource(crate::hot_reload::GameplayLibrary::new(path.into()));
+        }
 
-        let camera_controller = CameraController::new(0.2);
+        let mut engine = Self {
+            scene,
+            schedule: Schedule::new()
+        };
+        engine.add_default_systems();
+        engine
+    }
 
-        Self {
-            scene: Scene::new(),
-            camera_controller,
-            camera,
+    fn add_default_systems(&mut self) {
+        // Scripts can touch any component, so the script systems don't declare accesses and run alone.
+        // Independent scripts still run in parallel with each other inside them, see Script::is_independent
+        let systems = [
+            System::new("update_camera", Stage::PreUpdate, Self::update_camera)
+                .reads::<CameraController>()
+                .reads::<Time>()
+                .writes::<Camera>(),
+            System::new("fixed_update_scripts", Stage::FixedUpdate, |scene, _| scene.run_script_hook(|script, context| script.fixed_update(context))),
+            System::new("update_scripts", Stage::Update, |scene, _| scene.update_components()),
+            System::new("update_scripted_behaviours", Stage::Update, scripting::update_scripted_behaviours)
+                .after("update_scripts")
+                .reads::<ScriptingEngine>()
+                .reads::<Time>()
+                .reads::<Input>()
+                .reads::<ScriptEnabled>()
+                .writes::<ScriptedBehaviour>()
+                .writes::<Transform>()
+                .writes::<MeshInstance>(),
+            System::new("late_update_scripts", Stage::PostUpdate, |scene, _| scene.run_script_hook(|script, context| script.late_update(context))),
+            System::new("extract_camera", Stage::RenderExtract, Self::extract_camera)
+                .reads::<Camera>()
+                .writes::<ExtractedCamera>(),
+            System::new("extract_lod_groups", Stage::RenderExtract, |scene, _| {
+                if let Some(mut lod_groups) = scene.resource_mut::<ExtractedLodGroups>() {
+                    lod_groups.0 = scene.get_lod_groups();
+                }
+            })
+                .with_query::<(&LodGroup, Option<&Transform>)>()
+                .reads::<Parent>()
+                .writes::<ExtractedLodGroups>(),
+            // Applies the entity Transform to the mesh instances while extracting them, unchanged ones keep their instance data.
+            // Runs after the LOD groups so the renderer gets the groups before the instances they switch between
+            System::new("extract_mesh_instances", Stage::RenderExtract, |scene, ticks| {
+                if let Some(mut extracted) = scene.resource_mut::<ExtractedMeshInstances>() {
+                    extracted.mesh_instances = scene.get_changed_mesh_instances(ticks.last_run);
+                    extracted.removed_mesh_instances = scene.take_removed_mesh_instances();
+                }
+            })
+                .after("extract_lod_groups")
+                .with_query::<(&MeshInstance, Option<&Transform>)>()
+                .reads::<Parent>()
+                .writes::<ExtractedMeshInstances>()
+        ];
+
+        for system in systems {
+            self.add_system(system);
+        }
+    }
 
+    pub fn add_system(&mut self, system: System) {
+        if let Err(e) = self.schedule.add_system(system) {
+            warn!("Couldn't add system: {}", e);
         }
     }
 
@@ -39,7 +113,7 @@ impl Engine {
 
     pub fn setup(&mut self, renderer: &mut impl Renderer) {
         let test_script = TestScript::default();
-        let mut entity = self.scene.create_entity();
+        let entity = self.scene.create_entity();
         self.scene.add_script_to_entity(&entity, test_script);
 
 
@@ -60,36 +134,105 @@ impl Engine {
         }
 
 
-        entity = self.scene.create_entity();
+        let tree_prefab = self.scene.add_prefab(Prefab::new("Tree").with_mesh(mesh_index, Transform::default()));
 
-        let mesh_instance_index = renderer.get_mesh_manager_mut().create_mesh_instance(mesh_index);
-
-        if let Some(mesh_instance_index) = mesh_instance_index {
-            let mut transform = Transform::default();
-            transform.position.x = -4.0;
-
-            let mesh_instance = MeshInstance {
-                mesh_index,
-                mesh_instance_index,
-                local_transform: transform
-            };
-            self.scene.add_component_to_entity(&entity, mesh_instance);
+        let mut transform = Transform::default();
+        transform.position.x = -4.0;
+        if let Some(tree) = self.scene.spawn_prefab(tree_prefab, transform, renderer.get_mesh_manager_mut()) {
+            self.scene.add_component_to_entity(&tree, ScriptedBehaviour::new("spin.rhai"));
         }
 
+        #[cfg(feature = "hot_reload")]
+        self.spawn_library_scripts(tree_prefab, renderer);
+
         self.scene.setup_components();
         self.scene.update_components();
+
+        info!("System batches: {:?}", self.schedule.get_batch_names());
+    }
+
+    // Loads the gameplay library and puts each of its scripts on a tree, so they can be tried out right away
+    #[cfg(feature = "hot_reload")]
+    fn spawn_library_scripts(&mut self, prefab: crate::prefab::PrefabHandle, renderer: &mut impl Renderer) {
+        crate::hot_reload::update_gameplay_library(&mut self.scene);
+        let names = match self.scene.resource::<crate::hot_reload::GameplayLibrary>().map(|library| library.get_script_names()) {
+            Some(Ok(names)) => names,
+            Some(Err(e)) => {
+                warn!("{}", e);
+                return;
+            },
+            None => return
+        };
+
+        // The library links the engine, so its registry has the engine's scripts too
+        let engine_names = crate::script_registry::get_script_names();
+        for (i, name) in names.iter().filter(|name| !engine_names.contains(&name.as_str())).enumerate() {
+            let mut transform = Transform::default();
+            transform.position.x = 4.0 + 2.0 * i as f32;
+            if let Some(entity) = self.scene.spawn_prefab(prefab, transform, renderer.get_mesh_manager_mut()) {
+                self.scene.add_script_by_name(&entity, name);
+            }
+        }
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_controller.process_events(event)
+        if let Some(mut input) = self.scene.resource_mut::<Input>() {
+            input.process_events(event);
+        }
+        match self.scene.resource_mut::<CameraController>() {
+            Some(mut camera_controller) => camera_controller.process_events(event),
+            None => false
+        }
     }
 
-    pub fn update(&mut self, renderer_resources: &mut RendererResources) {
-        self.scene.update_components();
+    fn update_camera(scene: &Scene, _ticks: SystemTicks) {
+        let resources = (scene.resource::<CameraController>(), scene.resource_mut::<Camera>(), scene.resource::<Time>());
+        let (camera_controller, mut camera, time) = match resources {
+            (Some(camera_controller), Some(camera), Some(time)) => (camera_controller, camera, time),
+            _ => {
+                warn!("Couldn't find the camera resources");
+                return;
+            }
+        };
+
+        // The editor camera keeps moving while the game is paused or slowed down
+        camera_controller.update_camera(&mut camera, time.unscaled_delta);
+    }
+
+    fn extract_camera(scene: &Scene, _ticks: SystemTicks) {
+        let (camera, mut extracted) = match (scene.resource::<Camera>(), scene.resource_mut::<ExtractedCamera>()) {
+            (Some(camera), Some(extracted)) => (camera, extracted),
+            _ => {
+                warn!("Couldn't find the camera resources");
+                return;
+            }
+        };
+
+        extracted.camera_uniform.update_view_proj(&camera);
+        extracted.frustum = Frustum::from_view_projection(camera.build_view_projection_matrix());
+        extracted.position = camera.eye;
+        extracted.fovy = cgmath::Deg(camera.fovy);
+    }
 
-        let RendererResources { camera_uniform, .. } = renderer_resources;
+    pub fn update(&mut self, renderer_resources: &mut RendererResources) {
+        #[cfg(feature = "hot_reload")]
+        crate::hot_reload::update_gameplay_library(&mut self.scene);
+
+        self.scene.advance_change_tick();
+        self.scene.update_events();
+        let fixed_steps = match self.scene.resource_mut::<Time>() {
+            Some(mut time) => {
+                time.update();
+                time.fixed_steps
+            },
+            None => 0
+        };
+        self.schedule.run(&self.scene, fixed_steps);
+        self.scene.apply_commands();
+        renderer::move_extracted(&self.scene, renderer_resources);
+    }
 
-        self.camera_controller.update_camera(&mut self.camera);
-        camera_uniform.update_view_proj(&self.camera);
+    pub fn shutdown(&mut self) {
+        self.scene.destroy_scripts();
     }
 }
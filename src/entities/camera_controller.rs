@@ -3,6 +3,7 @@ use winit::event::{WindowEvent, KeyboardInput, ElementState, VirtualKeyCode};
 use crate::entities::Camera;
 
 pub struct CameraController {
+    // Units per second
     speed: f32,
     //forward-backward
     is_y_pressed: (bool, bool),
@@ -51,17 +52,18 @@ impl CameraController {
         }
     }
 
-    pub fn update_camera(&self, camera: &mut Camera) {
+    pub fn update_camera(&self, camera: &mut Camera, delta: f32) {
         use cgmath::InnerSpace;
+        let speed = self.speed * delta;
         let forward = camera.target - camera.eye;
         let forward_normalized = forward.normalize();
         let forward_magnitude = forward.magnitude();
 
-        if self.is_y_pressed.0 && forward_magnitude > self.speed {
-            camera.eye += forward_normalized * self.speed;
+        if self.is_y_pressed.0 && forward_magnitude > speed {
+            camera.eye += forward_normalized * speed;
         }
         if self.is_y_pressed.1 {
-            camera.eye -= forward_normalized * self.speed;
+            camera.eye -= forward_normalized * speed;
         }
 
         let right = forward_normalized.cross(camera.up);
@@ -70,11 +72,11 @@ impl CameraController {
         let forward_magnitude = forward.magnitude();
 
         if self.is_x_pressed.1 {
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_magnitude;
+            camera.eye = camera.target - (forward + right * speed).normalize() * forward_magnitude;
         }
 
         if self.is_x_pressed.0 {
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_magnitude;
+            camera.eye = camera.target - (forward - right * speed).normalize() * forward_magnitude;
         }
     }
 }
\ No newline at end of file
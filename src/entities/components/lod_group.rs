@@ -0,0 +1,54 @@
+use probable_spork_ecs::component::{Component, ComponentStorage};
+
+use crate::renderer::MeshManager;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LodThreshold {
+    // Level is used while the camera is closer than this
+    Distance(f32),
+    // Level is used while the bounding sphere covers more than this fraction of the screen height
+    ScreenSize(f32)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LodLevel {
+    pub mesh_index: usize,
+    pub mesh_instance_index: usize,
+    pub threshold: LodThreshold
+}
+
+// Every level owns an instance in its mesh, the renderer picks which one is shown each frame
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct LodGroup {
+    // Ordered from the most detailed level
+    pub levels: Vec<LodLevel>,
+    // Distance over which a level dithers into the next one
+    pub cross_fade: Option<f32>
+}
+
+impl LodGroup {
+    pub fn new(mesh_manager: &mut MeshManager, levels: &[(usize, LodThreshold)], cross_fade: Option<f32>) -> Option<LodGroup> {
+        let levels = levels.iter()
+            .map(|(mesh_index, threshold)| {
+                mesh_manager.create_mesh_instance(*mesh_index)
+                    .map(|mesh_instance_index| LodLevel {
+                        mesh_index: *mesh_index,
+                        mesh_instance_index,
+                        threshold: *threshold
+                    })
+            })
+            .collect::<Option<Vec<LodLevel>>>()?;
+
+        Some(Self {
+            levels,
+            cross_fade
+        })
+    }
+}
+
+impl Component for LodGroup {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
@@ -1,31 +1,27 @@
-use log::{info, warn};
-use probable_spork_ecs::{component::{Component, ComponentStorage, Entity}};
+use probable_spork_ecs::component::{Component, ComponentStorage};
 
-use crate::{renderer::TransformInstance, scene::Scene};
+use script_gen_macro::Reflect;
 
-use super::{Transform, MeshRenderer};
+use super::Transform;
 
-#[derive(Clone, PartialEq, Default, Debug)]
+#[derive(Clone, PartialEq, Default, Debug, Reflect)]
 pub struct MeshInstance {
     pub mesh_index: usize,
+    // Slot owned by the renderer, changing it would point at another instance
+    #[reflect(skip)]
     pub mesh_instance_index: usize,
     pub local_transform: Transform
 }
 
 impl MeshInstance {
-    pub fn apply_transforms_to_mesh_instance(scene: &Scene, entity_id: usize, mut mesh_instance: MeshInstance) -> MeshInstance {
-        match scene.component_storage.get_entity_component::<Transform>(&Entity(entity_id as u32)) {
-            Some(transform) => {
-                let transformed_t = Transform {
-                    position: mesh_instance.local_transform.position + transform.position,
-                    rotation: mesh_instance.local_transform.rotation * transform.rotation
-                };
-                mesh_instance.local_transform = transformed_t;
+    pub fn with_parent_transform(&self, transform: &Transform) -> MeshInstance {
+        let mut mesh_instance = self.clone();
+        mesh_instance.local_transform = Transform {
+            position: self.local_transform.position + transform.position,
+            rotation: self.local_transform.rotation * transform.rotation
+        };
 
-                mesh_instance
-            },
-            None => mesh_instance
-        }
+        mesh_instance
     }
 }
 
@@ -1,13 +1,24 @@
 use std::{error::Error, fmt::Display};
 
-use crate::{entities::CameraUniform, renderer::TransformInstance};
+use crate::{entities::CameraUniform, renderer::{TransformInstance, Frustum, CullingStats, BoundingSphere}, vertex::Vertex};
 
 pub trait MeshRenderer {
     fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+    fn set_material<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+    // Meshes with the same id can share a draw call
+    fn get_material_id(&self) -> (usize, usize);
     fn update_camera(&self, queue: &wgpu::Queue, camera_uniform_slice: &[CameraUniform]);
     fn update_instance_data(&mut self, instance_index: usize, transform: TransformInstance) -> Result<(), MeshRendererError>;
+    // Grows the instance buffer when the instances don't fit anymore, called before write_instance_data
+    fn reserve_instance_buffer(&mut self, device: &wgpu::Device);
     fn write_instance_data(&self, queue: &wgpu::Queue);
+    fn cull_instances(&mut self, frustum: &Frustum) -> CullingStats;
+    fn get_bounding_sphere(&self) -> &BoundingSphere;
+    fn get_vertices(&self) -> &[Vertex];
+    fn get_indices(&self) -> &[u16];
+    fn get_instances(&self) -> &[TransformInstance];
     fn create_instance(&mut self) -> usize;
+    fn remove_instance(&mut self, instance_index: usize) -> Result<(), MeshRendererError>;
 }
 
 #[derive(Debug)]
@@ -1,7 +1,19 @@
 mod mesh_renderer;
 mod transform;
 mod mesh_instance;
+mod lod_group;
+mod name;
+mod tags;
+mod prefab_instance;
+mod script_enabled;
+mod parent;
 
 pub use mesh_renderer::{MeshRenderer, MeshRendererError};
 pub use transform::Transform;
 pub use mesh_instance::MeshInstance;
+pub use lod_group::{LodGroup, LodLevel, LodThreshold};
+pub use name::Name;
+pub use tags::Tags;
+pub use prefab_instance::PrefabInstance;
+pub use script_enabled::ScriptEnabled;
+pub use parent::Parent;
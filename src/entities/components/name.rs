@@ -0,0 +1,15 @@
+use probable_spork_ecs::{component::{Component, ComponentStorage}, storage::StorageType};
+
+// Shown in the editor and used by Scene::find_by_name, not required to be unique
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Name(pub String);
+
+impl Component for Name {
+    // Every entity gets one from Scene::create_entity
+    const STORAGE_TYPE: StorageType = StorageType::Dense;
+
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
@@ -0,0 +1,12 @@
+use probable_spork_ecs::component::{Component, ComponentStorage, Entity};
+
+// The entity's Transform is relative to its parent's, despawning the parent despawns the entity too
+#[derive(Clone, PartialEq, Debug)]
+pub struct Parent(pub Entity);
+
+impl Component for Parent {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
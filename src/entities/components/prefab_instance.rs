@@ -0,0 +1,28 @@
+use std::any::TypeId;
+
+use probable_spork_ecs::component::{Component, ComponentStorage};
+
+use crate::prefab::PrefabHandle;
+
+// Added to entities spawned from a prefab. Components listed in overrides
+// were changed on this instance and are skipped when the prefab changes
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrefabInstance {
+    pub handle: PrefabHandle,
+    // Child indices leading from the prefab root to the node this entity was spawned from
+    pub node: Vec<usize>,
+    pub overrides: Vec<TypeId>
+}
+
+impl PrefabInstance {
+    pub fn is_overridden<T: 'static>(&self) -> bool {
+        self.overrides.contains(&TypeId::of::<T>())
+    }
+}
+
+impl Component for PrefabInstance {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
@@ -0,0 +1,18 @@
+use probable_spork_ecs::component::{Component, ComponentStorage};
+
+// Disabled scripts skip every update hook, toggled through Scene::set_script_enabled
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScriptEnabled(pub bool);
+
+impl Default for ScriptEnabled {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+impl Component for ScriptEnabled {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
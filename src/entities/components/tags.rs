@@ -0,0 +1,17 @@
+use probable_spork_ecs::component::{Component, ComponentStorage};
+
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|other| other == tag)
+    }
+}
+
+impl Component for Tags {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
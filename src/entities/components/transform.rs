@@ -1,9 +1,11 @@
-use cgmath::{Vector3, Quaternion};
-use probable_spork_ecs::{component::{Component, ComponentStorage}};
+use cgmath::{Vector3, Quaternion, Rotation};
+use probable_spork_ecs::{component::{Component, ComponentStorage}, storage::StorageType};
+
+use script_gen_macro::Reflect;
 
 use crate::renderer::TransformInstance;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Reflect)]
 pub struct Transform {
     pub position: Vector3<f32>,
     pub rotation: Quaternion<f32>
@@ -18,16 +20,30 @@ impl Default for Transform {
     }
 }
 
+impl Transform {
+    // This transform relative to the parent one, gives the world transform of a child entity
+    pub fn with_parent(&self, parent: &Transform) -> Transform {
+        Transform {
+            position: parent.position + parent.rotation.rotate_vector(self.position),
+            rotation: parent.rotation * self.rotation
+        }
+    }
+}
+
 impl From<&Transform> for TransformInstance {
     fn from(value: &Transform) -> Self {
         Self {
             position: value.position,
-            rotation: value.rotation
+            rotation: value.rotation,
+            fade: 1.0
         }
     }
 }
 
 impl Component for Transform {
+    // Nearly every entity has one
+    const STORAGE_TYPE: StorageType = StorageType::Dense;
+
     fn setup(&mut self, _world: &ComponentStorage) {
     }
     fn update(&mut self, _world: &ComponentStorage) {
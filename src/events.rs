@@ -0,0 +1,128 @@
+use std::{fmt::Debug, marker::PhantomData};
+
+use probable_spork_ecs::AtomicRefMut;
+
+use crate::scene::Scene;
+
+struct EventInstance<T> {
+    id: usize,
+    event: T
+}
+
+// Double buffered queue, events sent during a frame stay readable until the end of the next one
+pub struct Events<T> {
+    previous: Vec<EventInstance<T>>,
+    current: Vec<EventInstance<T>>,
+    event_count: usize
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            previous: vec![],
+            current: vec![],
+            event_count: 0
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(EventInstance { id: self.event_count, event });
+        self.event_count += 1;
+    }
+
+    // Drops the events of the frame before last
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &EventInstance<T>> {
+        self.previous.iter().chain(self.current.iter())
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct EventWriter<'w, T> {
+    events: AtomicRefMut<'w, Events<T>>
+}
+
+impl<'w, T: 'static> EventWriter<'w, T> {
+    pub fn new(events: AtomicRefMut<'w, Events<T>>) -> Self {
+        Self {
+            events
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+}
+
+// Keeps track of what it has already read, every reader sees each event once
+pub struct EventReader<T> {
+    next_id: usize,
+    _marker: PhantomData<T>
+}
+
+impl<T: Clone + 'static> EventReader<T> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            _marker: PhantomData
+        }
+    }
+
+    pub fn read(&mut self, scene: &Scene) -> Vec<T> {
+        match scene.resource::<Events<T>>() {
+            Some(events) => {
+                let unread: Vec<T> = events.iter()
+                    .filter(|instance| instance.id >= self.next_id)
+                    .map(|instance| instance.event.clone())
+                    .collect();
+                self.next_id = events.event_count;
+                unread
+            },
+            None => vec![]
+        }
+    }
+}
+
+impl<T: Clone + 'static> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Type erased handle of a registered event type, used for the per-frame swap and the editor.
+// The name is owned, the type could come from a gameplay library that gets unloaded
+pub struct EventChannel {
+    pub type_name: String,
+    update: fn(&Scene),
+    get_pending: fn(&Scene) -> Vec<String>
+}
+
+impl EventChannel {
+    pub fn of<T: Debug + 'static>() -> Self {
+        Self {
+            type_name: std::any::type_name::<T>().to_string(),
+            update: |scene| if let Some(mut events) = scene.resource_mut::<Events<T>>() {
+                events.update();
+            },
+            get_pending: |scene| scene.resource::<Events<T>>()
+                .map(|events| events.iter().map(|instance| format!("{:?}", instance.event)).collect())
+                .unwrap_or_default()
+        }
+    }
+
+    pub fn update(&self, scene: &Scene) {
+        (self.update)(scene);
+    }
+
+    pub fn get_pending(&self, scene: &Scene) -> Vec<String> {
+        (self.get_pending)(scene)
+    }
+}
@@ -0,0 +1,232 @@
+// Gameplay scripts loaded from a dynamic library that can be rebuilt while the engine runs, see gameplay/.
+// The library links its own copy of the engine, so it has to be built with the same compiler, features and profile.
+// It exports
+//     #[no_mangle] pub fn create_script(name: &str) -> Option<Box<dyn Script>>
+//     #[no_mangle] pub fn get_script_names() -> Vec<String>
+//     #[no_mangle] pub fn set_logger(logger: &'static dyn log::Log, level: log::LevelFilter)
+// create_script returns a default instance of the script type called `name`
+use std::{error::Error, fmt::Display, fs, path::{Path, PathBuf}, time::SystemTime};
+
+use log::{info, warn};
+use probable_spork_ecs::component::{Component, ComponentStorage, Entity};
+use script_gen_macro::{ScriptComponentUpdater, Reflect};
+
+use crate::{scene::Scene, script::{Script, ScriptContext, ScriptComponentUpdater}};
+
+type CreateScriptFn = fn(&str) -> Option<Box<dyn Script>>;
+type GetScriptNamesFn = fn() -> Vec<String>;
+type SetLoggerFn = fn(&'static dyn log::Log, log::LevelFilter);
+const CREATE_SCRIPT_SYMBOL: &[u8] = b"create_script";
+const GET_SCRIPT_NAMES_SYMBOL: &[u8] = b"get_script_names";
+const SET_LOGGER_SYMBOL: &[u8] = b"set_logger";
+
+#[derive(Debug)]
+pub enum HotReloadError {
+    NotLoaded,
+    Copy(std::io::Error),
+    Load(libloading::Error)
+}
+
+impl Display for HotReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HotReloadError::NotLoaded => write!(f, "Gameplay library isn't loaded"),
+            HotReloadError::Copy(e) => write!(f, "Couldn't copy the gameplay library: {}", e),
+            HotReloadError::Load(e) => write!(f, "Couldn't load the gameplay library: {}", e)
+        }
+    }
+}
+
+impl Error for HotReloadError {}
+
+// Marks entities whose script was created by the gameplay library
+pub struct LibraryScript;
+
+impl Component for LibraryScript {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
+
+// Replaces library scripts whose type is gone from the reloaded library,
+// nothing from the old library can be alive once it's unloaded
+#[derive(ScriptComponentUpdater, Reflect, Default)]
+struct UnloadedScript {
+    entity: Entity
+}
+
+impl Script for UnloadedScript {
+    fn script_setup(&mut self, _context: &ScriptContext) {
+    }
+    fn script_update(&mut self, _context: &ScriptContext) {
+    }
+}
+
+fn remove_copy(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        warn!("Couldn't remove gameplay library copy {:?}: {}", path, e);
+    }
+}
+
+// A loaded copy of the gameplay library, the file is removed once the library is unloaded
+struct LoadedCopy {
+    // Only None while dropping
+    library: Option<libloading::Library>,
+    path: PathBuf
+}
+
+impl LoadedCopy {
+    fn get(&self) -> &libloading::Library {
+        self.library.as_ref().expect("Library is only taken on drop")
+    }
+}
+
+impl Drop for LoadedCopy {
+    fn drop(&mut self) {
+        drop(self.library.take());
+        remove_copy(&self.path);
+    }
+}
+
+// Scene resource, inserted by the engine when GAMEPLAY_LIBRARY is set
+pub struct GameplayLibrary {
+    path: PathBuf,
+    library: Option<LoadedCopy>,
+    modified: Option<SystemTime>,
+    load_count: u32
+}
+
+impl GameplayLibrary {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            library: None,
+            modified: None,
+            load_count: 0
+        }
+    }
+
+    fn get_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    // Also true before the first load
+    pub fn has_changed(&self) -> bool {
+        self.get_modified().is_some_and(|modified| Some(modified) != self.modified)
+    }
+
+    // Loads a copy, so cargo can overwrite the original while it's in use.
+    // The modified time is taken first, a library that fails to load is retried on its next change
+    fn open(&mut self) -> Result<LoadedCopy, HotReloadError> {
+        self.modified = self.get_modified();
+        let mut loaded_path = self.path.clone().into_os_string();
+        loaded_path.push(format!(".{}", self.load_count));
+        let loaded_path = PathBuf::from(loaded_path);
+        self.load_count += 1;
+        fs::copy(&self.path, &loaded_path).map_err(HotReloadError::Copy)?;
+
+        // Runs the library's initializers, it has to be a gameplay library built for this engine
+        let library = match unsafe { libloading::Library::new(&loaded_path) } {
+            Ok(library) => library,
+            Err(e) => {
+                remove_copy(&loaded_path);
+                return Err(HotReloadError::Load(e));
+            }
+        };
+        match unsafe { library.get::<SetLoggerFn>(SET_LOGGER_SYMBOL) } {
+            Ok(set_logger) => set_logger(log::logger(), log::max_level()),
+            Err(_) => warn!("Gameplay library doesn't export set_logger, its logs are dropped")
+        }
+        info!("Loaded gameplay library {:?}", loaded_path);
+        Ok(LoadedCopy { library: Some(library), path: loaded_path })
+    }
+
+    fn get_library(&self) -> Result<&libloading::Library, HotReloadError> {
+        self.library.as_ref().map(LoadedCopy::get).ok_or(HotReloadError::NotLoaded)
+    }
+
+    pub fn create_script(&self, name: &str) -> Result<Option<Box<dyn Script>>, HotReloadError> {
+        let create_script = unsafe { self.get_library()?.get::<CreateScriptFn>(CREATE_SCRIPT_SYMBOL) }.map_err(HotReloadError::Load)?;
+        Ok(create_script(name))
+    }
+
+    pub fn get_script_names(&self) -> Result<Vec<String>, HotReloadError> {
+        let get_script_names = unsafe { self.get_library()?.get::<GetScriptNamesFn>(GET_SCRIPT_NAMES_SYMBOL) }.map_err(HotReloadError::Load)?;
+        Ok(get_script_names())
+    }
+
+    // Loads the library the first time, afterwards swaps every library script for one from the new library.
+    // Script state is carried over through Reflect. The old library stays loaded when the new one fails,
+    // a replaced one is unloaded and its copy removed
+    pub fn reload(&mut self, scene: &mut Scene) -> Result<(), HotReloadError> {
+        let library = self.open()?;
+        let create_script = *unsafe { library.get().get::<CreateScriptFn>(CREATE_SCRIPT_SYMBOL) }.map_err(HotReloadError::Load)?;
+
+        let entities: Vec<Entity> = scene.component_storage.iter::<LibraryScript>().map(|(entity, _)| entity).collect();
+        for entity in entities {
+            let (type_name, value) = match scene.component_storage.get_entity_component_mut::<Box<dyn Script>>(&entity) {
+                Some(mut script) => {
+                    // Synced components could have changed since the script's last hook
+                    script.pre_user_update(&scene.component_storage);
+                    // The type name lives in the old library, it has to be copied before unloading
+                    (script.get_type_name().to_string(), script.to_value())
+                },
+                None => continue
+            };
+
+            let mut script = match create_script(&type_name) {
+                Some(mut script) => {
+                    if let Err(e) = script.apply_value(value) {
+                        warn!("Couldn't restore {} on entity {}: {}", type_name, entity, e);
+                    }
+                    script
+                },
+                None => {
+                    warn!("Reloaded gameplay library doesn't have a script called {}", type_name);
+                    scene.remove_component::<LibraryScript>(&entity);
+                    Box::new(UnloadedScript::default())
+                }
+            };
+            script.pre_setup(entity, &mut scene.component_storage);
+            // Drops the old script while its library is still loaded
+            scene.component_storage.register_component(&entity, script);
+        }
+
+        self.library = Some(library);
+        Ok(())
+    }
+}
+
+// Returns false when the library doesn't have the script
+pub fn add_library_script(scene: &mut Scene, entity: &Entity, name: &str) -> bool {
+    let script = match scene.resource::<GameplayLibrary>().map(|library| library.create_script(name)) {
+        Some(Ok(Some(script))) => script,
+        Some(Ok(None)) => {
+            warn!("Gameplay library doesn't have a script called {}", name);
+            return false;
+        },
+        Some(Err(e)) => {
+            warn!("{}", e);
+            return false;
+        },
+        None => return false
+    };
+    scene.add_boxed_script_to_entity(entity, script);
+    scene.add_component_to_entity(entity, LibraryScript);
+    true
+}
+
+// Checked once per frame, the first call loads the library
+pub fn update_gameplay_library(scene: &mut Scene) {
+    let mut library = match scene.remove_resource::<GameplayLibrary>() {
+        Some(library) => library,
+        None => return
+    };
+    if library.has_changed() {
+        if let Err(e) = library.reload(scene) {
+            warn!("{}", e);
+        }
+    }
+    scene.insert_resource(library);
+}
@@ -0,0 +1,39 @@
+use std::collections::HashSet;
+
+use winit::event::{WindowEvent, KeyboardInput, ElementState, VirtualKeyCode};
+
+// Keys currently held down, a scene resource updated from the window events
+#[derive(Clone, Default)]
+pub struct Input {
+    pressed: HashSet<VirtualKeyCode>
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Doesn't consume the event, the camera controller and editor still get it
+    pub fn process_events(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(keycode), .. }, .. } => {
+                match state {
+                    ElementState::Pressed => self.pressed.insert(*keycode),
+                    ElementState::Released => self.pressed.remove(keycode)
+                };
+            },
+            // Keys released while the window is unfocused never send a Released event
+            WindowEvent::Focused(false) => self.pressed.clear(),
+            _ => {}
+        }
+    }
+
+    pub fn is_pressed(&self, keycode: VirtualKeyCode) -> bool {
+        self.pressed.contains(&keycode)
+    }
+
+    // Key names match the VirtualKeyCode variants, e.g. "W" or "Space"
+    pub fn is_pressed_by_name(&self, name: &str) -> bool {
+        self.pressed.iter().any(|keycode| format!("{:?}", keycode) == name)
+    }
+}
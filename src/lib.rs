@@ -0,0 +1,54 @@
+// The engine is a library so gameplay crates built as a cdylib can depend on it, see hot_reload.rs.
+// Lets derives from script_gen_macro use the same absolute paths in the engine and in gameplay crates
+extern crate self as probable_spork_r;
+
+pub mod texture;
+pub mod entities;
+pub mod vertex;
+pub mod shader;
+pub mod errors;
+pub mod renderer;
+pub mod test_tree;
+pub mod assets;
+pub mod engine;
+pub mod editor;
+pub mod script;
+pub mod scene;
+pub mod query;
+pub mod schedule;
+pub mod resources;
+pub mod events;
+pub mod prefab;
+pub mod reflect;
+pub mod time;
+pub mod commands;
+pub mod input;
+pub mod scripting;
+pub mod script_registry;
+pub mod topological_sort;
+#[cfg(feature = "hot_reload")]
+pub mod hot_reload;
+
+use entities::{CameraUniform, components::{MeshInstance, LodGroup, Transform}};
+use renderer::{Frustum, CullingStats};
+
+pub struct WgpuStructs {
+    pub surface: wgpu::Surface,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub config: wgpu::SurfaceConfiguration,
+    pub sample_count: u32,
+    pub downlevel_flags: wgpu::DownlevelFlags
+}
+
+pub struct RendererResources {
+    pub camera_uniform: CameraUniform,
+    pub frustum: Frustum,
+    pub culling_stats: CullingStats,
+    pub camera_position: cgmath::Point3<f32>,
+    pub camera_fovy: cgmath::Deg<f32>,
+    // Filled by the RenderExtract stage
+    pub mesh_instances: Vec<MeshInstance>,
+    pub removed_mesh_instances: Vec<MeshInstance>,
+    pub lod_groups: Vec<(LodGroup, Transform)>
+}
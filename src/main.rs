@@ -1,63 +1,40 @@
-mod texture;
-mod entities;
-mod vertex;
-mod shader;
-mod errors;
-mod renderer;
-mod test_tree;
-mod assets;
-mod engine;
-mod editor;
-mod script;
-mod scene;
-
 use std::sync::Arc;
 use log::{info, warn};
-use probable_spork_ecs::component::Component;
-use renderer::TexturedMesh;
-use entities::{CameraUniform, components::MeshRenderer};
-use renderer::{Renderer};
-use shader::{Shader, ShaderBuilder};
-use texture::Texture;
 use wgpu::{InstanceDescriptor, RequestAdapterOptions};
 use winit::{event_loop::{EventLoop, ControlFlow}, window::WindowBuilder, event::{Event, WindowEvent, KeyboardInput, ElementState, VirtualKeyCode}, dpi::LogicalSize};
 use winit::window::Window;
-use test_tree::{VERTICES, INDICES};
-
-use crate::{editor::Editor};
-use crate::engine::Engine;
-use crate::renderer::EditorRenderer;
-
-pub struct WgpuStructs {
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration
-}
 
-pub struct RendererResources {
-    camera_uniform: CameraUniform,
-}
+use probable_spork_r::{WgpuStructs, RendererResources};
+use probable_spork_r::entities::CameraUniform;
+use probable_spork_r::renderer::{Renderer, Frustum, CullingStats, TexturedMesh, EditorRenderer};
+use probable_spork_r::shader::{Shader, ShaderBuilder};
+use probable_spork_r::texture::Texture;
+use probable_spork_r::test_tree::{VERTICES, INDICES};
+use probable_spork_r::editor::Editor;
+use probable_spork_r::engine::Engine;
+
+// Scene pass MSAA, one of 1/2/4/8. Gets lowered if the adapter can't do it
+const MSAA_SAMPLE_COUNT: u32 = 4;
+const SUPPORTED_SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+// Packs every mesh into shared buffers, culls on the GPU and draws with indirect calls
+const INDIRECT_DRAWING: bool = false;
 
 struct App {
     window: Window,
     wgpu_structs: WgpuStructs,
     size: winit::dpi::PhysicalSize<u32>,
     pixels_per_point: f32,
-    shaders: Vec<Arc<Shader>>
+    shaders: Vec<Arc<Shader>>,
+    // Loaded once and shared, meshes with the same shader and texture get batched by the indirect drawer
+    tree_texture: Option<Arc<Texture>>
 }
 
 impl App {
-    async fn new(window: Window) -> App {
+    async fn new(window: Window, requested_sample_count: u32) -> App {
         let size = window.inner_size();
         let pixels_per_point = window.scale_factor() as f32;
 
         let backends = wgpu::Backends::all();
-        let device_descriptor = wgpu::DeviceDescriptor {
-            features: wgpu::Features::empty(),
-            limits: wgpu::Limits::default(),
-            label: None
-        };
         let power_preference = wgpu::PowerPreference::HighPerformance;
 
         let instance = wgpu::Instance::new(
@@ -75,14 +52,23 @@ impl App {
             force_fallback_adapter: false
         }).await.unwrap();
 
+        // Without this only the sample counts guaranteed by WebGPU (1 and 4) are usable
+        // Multi draw is optional for indirect drawing, it falls back to one indirect call per mesh
+        let features = adapter.features() & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+            | wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE);
+        let device_descriptor = wgpu::DeviceDescriptor {
+            features,
+            limits: wgpu::Limits::default(),
+            label: None
+        };
+
         let (device, queue) = adapter.request_device(&device_descriptor, None).await.unwrap();
 
         let surface_caps = surface.get_capabilities(&adapter);
         let present_mode = surface_caps.present_modes[0];
         let surface_format = surface_caps.formats.iter()
             .copied()
-            .filter(|f| f.describe().srgb)
-            .next()
+            .find(|f| f.describe().srgb)
             .unwrap_or(surface_caps.formats[0]);
         info!("Surface format: {:?}", surface_format);
 
@@ -97,13 +83,17 @@ impl App {
         };
         surface.configure(&device, &config);
 
-
+        let downlevel_flags = adapter.get_downlevel_capabilities().flags;
+        let sample_count = Self::get_supported_sample_count(&adapter, features, &[surface_format, Texture::DEPTH_FORMAT], requested_sample_count);
+        info!("MSAA sample count: {}", sample_count);
 
         let wgpu_structs = WgpuStructs {
             device,
             config,
             surface,
-            queue
+            queue,
+            sample_count,
+            downlevel_flags
         };
 
         Self {
@@ -112,9 +102,42 @@ impl App {
             size,
             pixels_per_point,
             shaders: vec![],
+            tree_texture: None
         }
     }
 
+    fn get_supported_sample_count(adapter: &wgpu::Adapter, features: wgpu::Features, formats: &[wgpu::TextureFormat],
+        requested_sample_count: u32) -> u32 {
+        if !SUPPORTED_SAMPLE_COUNTS.contains(&requested_sample_count) {
+            warn!("Unsupported MSAA sample count ({}), expected one of {:?}", requested_sample_count, SUPPORTED_SAMPLE_COUNTS);
+        }
+
+        let is_supported = |sample_count: u32| formats.iter().all(|format| {
+            let format_features = if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                adapter.get_texture_format_features(*format)
+            } else {
+                format.describe().guaranteed_format_features
+            };
+
+            let can_resolve = sample_count == 1 || format.describe().sample_type == wgpu::TextureSampleType::Depth
+                || format_features.flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+
+            format_features.flags.sample_count_supported(sample_count) && can_resolve
+        });
+
+        let sample_count = SUPPORTED_SAMPLE_COUNTS.iter()
+            .rev()
+            .copied()
+            .filter(|sample_count| *sample_count <= requested_sample_count)
+            .find(|sample_count| is_supported(*sample_count))
+            .unwrap_or(1);
+
+        if sample_count != requested_sample_count {
+            warn!("MSAA sample count {} isn't supported by the adapter, falling back to {}", requested_sample_count, sample_count);
+        }
+        sample_count
+    }
+
     fn get_shader_by_label(&self, label: &str) -> Option<Arc<Shader>> {
         self.shaders.iter()
             .find(|shader| shader.label == label)
@@ -124,10 +147,13 @@ impl App {
     fn create_mesh(&mut self) -> Option<TexturedMesh> {
         let WgpuStructs { device, queue, .. } = &self.wgpu_structs;
 
-        let texture_bytes = include_bytes!("textures/happy-tree.png");
-        let diffuse_texture = Texture::from_bytes(texture_bytes, device, queue, "Tree texture").unwrap();
+        let diffuse_texture = self.tree_texture.get_or_insert_with(|| {
+            let texture_bytes = include_bytes!("textures/happy-tree.png");
+            let texture = Texture::from_bytes(texture_bytes, device, queue, "Tree texture").unwrap();
+            info!("Tree texture format: {:?}", texture.texture.format());
+            Arc::new(texture)
+        }).clone();
 
-        info!("Tree texture format: {:?}", diffuse_texture.texture.format());
         let default_shader = self.get_shader_by_label("basic_shader.wgsl");
 
         match default_shader {
@@ -151,21 +177,21 @@ impl App {
     }
 
     fn init_shaders(&mut self) -> Result<(), anyhow::Error> {
-        let WgpuStructs { device, config, .. } = &self.wgpu_structs;
+        let WgpuStructs { device, config, sample_count, .. } = &self.wgpu_structs;
 
         let basic_shader = ShaderBuilder::new()
             .load_shader(device, "basic_shader.wgsl")?
             .add_texture(device, "texture")
             .add_uniform::<CameraUniform>(device, "camera_uniform", CameraUniform::new())
             //TODO - Replace with logger
-            .build(device, config).expect("Failed to build shader");
+            .build(device, config, *sample_count).expect("Failed to build shader");
 
         self.shaders.push(Arc::new(basic_shader));
         Ok(())
     }
 
 
-    fn resize_window(&mut self, new_size: winit::dpi::PhysicalSize<u32>) -> Option<Texture> {
+    fn resize_window(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         let WgpuStructs { config, device, surface, .. } = &mut self.wgpu_structs;
 
         if new_size.width > 0 && new_size.height > 0 {
@@ -173,10 +199,7 @@ impl App {
             config.width = new_size.width;
             config.height = new_size.height;
             surface.configure(device, config);
-            let depth_texture = Texture::create_depth_texture(device, config, "Depth texture");
-            return Some(depth_texture)
         }
-        None
     }
 }
 
@@ -190,7 +213,7 @@ async fn start() {
         .build(&event_loop).unwrap();
 
     {
-        let mut app = App::new(window).await;
+        let mut app = App::new(window, MSAA_SAMPLE_COUNT).await;
         let mut engine = Engine::new(&app.wgpu_structs.config);
         let mut editor = Editor::new(&event_loop, &app.window);
 
@@ -219,18 +242,18 @@ async fn start() {
 
         let pixels_per_point = editor.pixels_per_point;
         let mut renderer = EditorRenderer::new(&app.wgpu_structs, &app.window, app.wgpu_structs.config.format, pixels_per_point).await;
-        //let mut renderer = MainRenderer::new(&app.wgpu_structs.device, &app.wgpu_structs.config);
+        //let mut renderer = MainRenderer::new();
+        if INDIRECT_DRAWING {
+            renderer.get_mesh_manager_mut().enable_indirect_drawing(&app.wgpu_structs);
+        }
         if let Some(mesh) = mesh {
             renderer.add_mesh(mesh);
         }
 
         engine.setup(&mut renderer);
         event_loop.run(move |event, _, control_flow| match event {
-            Event::WindowEvent { ref event, window_id,} if window_id == app.window.id() => if !engine.input(event) {
-
-
+            Event::WindowEvent { ref event, window_id,} if window_id == app.window.id() && !engine.input(event) => {
                 let event_response = editor.handle_event(event);
-                
                 if !event_response.consumed {
                     match event {
                         WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
@@ -238,35 +261,44 @@ async fn start() {
                             input:
                                 KeyboardInput{
                                     state: ElementState::Pressed,
-                                    virtual_keycode: Some(keycode),
+                                    virtual_keycode: Some(VirtualKeyCode::Escape),
                                     ..
                                 },
                                 ..
-                        } => match keycode {
-                            VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
-                            _ => {}
-                        },
+                        } => *control_flow = ControlFlow::Exit,
                         WindowEvent::Resized(physical_size) => {
-                            let depth_texture = app.resize_window(*physical_size);
-                            renderer.resize(*physical_size, None, depth_texture);
+                            app.resize_window(*physical_size);
+                            renderer.resize(*physical_size, None);
                         },
                         WindowEvent::ScaleFactorChanged { new_inner_size, scale_factor } => {
-                            let depth_texture = app.resize_window(**new_inner_size);
-                            renderer.resize(**new_inner_size, Some(*scale_factor as f32), depth_texture);
+                            app.resize_window(**new_inner_size);
+                            renderer.resize(**new_inner_size, Some(*scale_factor as f32));
                         },
                         _ => {}
                     }
                 }
             },
             Event::MainEventsCleared => app.window.request_redraw(),
+            Event::LoopDestroyed => engine.shutdown(),
             Event::RedrawRequested(window_id) if window_id == app.window.id() => {
 
                 let mut renderer_resources = RendererResources {
                     camera_uniform: CameraUniform::new(),
+                    frustum: Frustum::default(),
+                    culling_stats: CullingStats::default(),
+                    camera_position: cgmath::Point3::new(0.0, 0.0, 0.0),
+                    camera_fovy: cgmath::Deg(45.0),
+                    mesh_instances: vec![],
+                    removed_mesh_instances: vec![],
+                    lod_groups: vec![]
                 };
 
                 engine.update(&mut renderer_resources);
-                renderer.update_meshes(engine.scene.get_mesh_instances());
+                for mesh_instance in std::mem::take(&mut renderer_resources.removed_mesh_instances) {
+                    renderer.get_mesh_manager_mut().remove_mesh_instance(mesh_instance.mesh_index, mesh_instance.mesh_instance_index);
+                }
+                renderer.update_lod_groups(std::mem::take(&mut renderer_resources.lod_groups), &renderer_resources);
+                renderer.update_meshes(std::mem::take(&mut renderer_resources.mesh_instances), &mut renderer_resources);
 
                 let editor_output = editor.draw(&app.window, &renderer_resources, &engine.scene);
                 renderer.update_ui(editor_output.0, editor_output.1);
@@ -274,8 +306,8 @@ async fn start() {
                 match renderer.render(&app.wgpu_structs, &app.window, &mut renderer_resources) {
                     Ok(_) => {},
                     Err(wgpu::SurfaceError::Lost) => {
-                        let depth_texture = app.resize_window(app.size);
-                        renderer.resize(app.size, Some(app.pixels_per_point), depth_texture);
+                        app.resize_window(app.size);
+                        renderer.resize(app.size, Some(app.pixels_per_point));
                     },
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                     Err(e) => println!("Err: {:?}", e)
@@ -0,0 +1,176 @@
+use std::any::TypeId;
+
+use probable_spork_ecs::component::{Component, Entity};
+
+use crate::{scene::Scene, script::Script, entities::components::Transform};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PrefabHandle(pub usize);
+
+trait PrefabComponent: Send + Sync {
+    fn get_type_id(&self) -> TypeId;
+    fn add_to_entity(&self, scene: &mut Scene, entity: &Entity);
+    fn apply_to_entity(&self, scene: &mut Scene, entity: &Entity);
+}
+
+struct PrefabValue<T>(T);
+
+impl<T: Component + Clone + 'static> PrefabComponent for PrefabValue<T> {
+    fn get_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn add_to_entity(&self, scene: &mut Scene, entity: &Entity) {
+        scene.add_component_to_entity(entity, self.0.clone());
+    }
+
+    // Instances spawned before the component was added to the prefab don't have it yet
+    fn apply_to_entity(&self, scene: &mut Scene, entity: &Entity) {
+        let has_component = scene.component_storage.get_entity_component::<T>(entity).is_some();
+        match has_component {
+            true => scene.update_entity_component(entity, self.0.clone()),
+            false => scene.add_component_to_entity(entity, self.0.clone())
+        }
+    }
+}
+
+// Adds a freshly constructed script to a spawned entity
+type AddScript = Box<dyn Fn(&mut Scene, &Entity) + Send + Sync>;
+
+// Template for an entity subtree. Components are cloned into every instance, scripts are
+// constructed fresh and the mesh gets its own renderer instance per spawned entity.
+// Children are spawned with a Parent component, their Transform is relative to the parent
+pub struct Prefab {
+    pub name: String,
+    components: Vec<Box<dyn PrefabComponent>>,
+    scripts: Vec<AddScript>,
+    mesh: Option<(usize, Transform)>,
+    children: Vec<Prefab>
+}
+
+impl Prefab {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            components: vec![],
+            scripts: vec![],
+            mesh: None,
+            children: vec![]
+        }
+    }
+
+    // Replaces a component of the same type
+    pub fn with_component<T: Component + Clone + 'static>(mut self, component: T) -> Self {
+        self.set_component(component);
+        self
+    }
+
+    pub fn with_script<T: Script + Default + 'static>(mut self) -> Self {
+        self.scripts.push(Box::new(|scene, entity| scene.add_script_to_entity(entity, T::default())));
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh_index: usize, local_transform: Transform) -> Self {
+        self.mesh = Some((mesh_index, local_transform));
+        self
+    }
+
+    pub fn with_child(mut self, child: Prefab) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub(crate) fn get_children(&self) -> &[Prefab] {
+        &self.children
+    }
+
+    // Node reached by following the child indices from this prefab
+    pub(crate) fn get_node_mut(&mut self, node: &[usize]) -> Option<&mut Prefab> {
+        match node.split_first() {
+            Some((child_index, rest)) => self.children.get_mut(*child_index)?.get_node_mut(rest),
+            None => Some(self)
+        }
+    }
+
+    pub(crate) fn set_mesh(&mut self, mesh: Option<(usize, Transform)>) {
+        self.mesh = mesh;
+    }
+
+    pub(crate) fn set_component<T: Component + Clone + 'static>(&mut self, component: T) {
+        self.components.retain(|other| other.get_type_id() != TypeId::of::<T>());
+        self.components.push(Box::new(PrefabValue(component)));
+    }
+
+    pub(crate) fn has_component(&self, type_id: TypeId) -> bool {
+        self.components.iter().any(|component| component.get_type_id() == type_id)
+    }
+
+    pub(crate) fn get_mesh(&self) -> Option<&(usize, Transform)> {
+        self.mesh.as_ref()
+    }
+
+    pub(crate) fn add_to_entity(&self, scene: &mut Scene, entity: &Entity) {
+        self.components.iter().for_each(|component| component.add_to_entity(scene, entity));
+        self.scripts.iter().for_each(|add_script| add_script(scene, entity));
+    }
+
+    pub(crate) fn apply_component_to_entity(&self, type_id: TypeId, scene: &mut Scene, entity: &Entity) {
+        self.components.iter()
+            .filter(|component| component.get_type_id() == type_id)
+            .for_each(|component| component.apply_to_entity(scene, entity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::{entities::components::{Parent, PrefabInstance}, renderer::MeshManager};
+    use super::*;
+
+    fn at(x: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.position.x = x;
+        transform
+    }
+
+    fn spawn_tree(scene: &mut Scene) -> (PrefabHandle, Entity, Entity) {
+        let handle = scene.add_prefab(Prefab::new("Tree").with_child(Prefab::new("Leaves").with_component(at(1.0))));
+        let root = scene.spawn_prefab(handle, at(10.0), &mut MeshManager::new()).unwrap();
+        let child = scene.get_children(&root)[0];
+        (handle, root, child)
+    }
+
+    #[test]
+    fn spawns_the_subtree() {
+        let mut scene = Scene::new();
+        let (_, root, child) = spawn_tree(&mut scene);
+
+        assert_eq!(scene.component_storage.get_entity_component::<Parent>(&child).map(|parent| parent.0), Some(root));
+        assert_eq!(scene.component_storage.get_entity_component::<PrefabInstance>(&child).map(|instance| instance.node.clone()), Some(vec![0]));
+        assert_eq!(scene.get_world_transform(&child).position, Vector3::new(11.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn child_edits_respect_overrides() {
+        let mut scene = Scene::new();
+        let (handle, _, child) = spawn_tree(&mut scene);
+        // Second instance of the same prefab
+        let root = scene.spawn_prefab(handle, at(20.0), &mut MeshManager::new()).unwrap();
+        let overridden_child = scene.get_children(&root)[0];
+        scene.override_component(&overridden_child, at(5.0));
+
+        scene.set_prefab_node_component(handle, &[0], at(2.0));
+        let position = |entity: &Entity| scene.component_storage.get_entity_component::<Transform>(entity).map(|transform| transform.position.x);
+        assert_eq!(position(&child), Some(2.0));
+        assert_eq!(position(&overridden_child), Some(5.0));
+    }
+
+    #[test]
+    fn despawning_the_root_despawns_children() {
+        let mut scene = Scene::new();
+        let (_, root, child) = spawn_tree(&mut scene);
+        scene.despawn(&root);
+        assert!(!scene.is_alive(&child));
+    }
+}
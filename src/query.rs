@@ -0,0 +1,262 @@
+use std::{any::TypeId, borrow::Cow, error::Error, fmt::Display, marker::PhantomData};
+
+use probable_spork_ecs::{component::{Component, Entity}, change_detection::Mut, AtomicRef};
+
+use crate::scene::Scene;
+
+pub struct Access {
+    type_id: TypeId,
+    type_name: &'static str,
+    is_mut: bool
+}
+
+impl Access {
+    pub fn of<T: 'static>(is_mut: bool) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            is_mut
+        }
+    }
+
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        self.type_id == other.type_id && (self.is_mut || other.is_mut)
+    }
+}
+
+// Something a query can fetch for a single entity, a component reference, an optional one or the Entity itself
+pub trait QueryParam<'w> {
+    type Item;
+    fn fetch(scene: &'w Scene, entity: &Entity) -> Option<Self::Item>;
+    fn access(accesses: &mut Vec<Access>);
+    // Components every fetched entity must have, the query only visits the smallest of their storages
+    fn required(_types: &mut Vec<TypeId>) {
+    }
+}
+
+impl<'w> QueryParam<'w> for Entity {
+    type Item = Entity;
+    fn fetch(_scene: &'w Scene, entity: &Entity) -> Option<Self::Item> {
+        Some(*entity)
+    }
+    fn access(_accesses: &mut Vec<Access>) {
+    }
+}
+
+impl<'w, T: Component + 'static> QueryParam<'w> for &T {
+    type Item = AtomicRef<'w, T>;
+    fn fetch(scene: &'w Scene, entity: &Entity) -> Option<Self::Item> {
+        scene.component_storage.get_entity_component::<T>(entity)
+    }
+    fn access(accesses: &mut Vec<Access>) {
+        accesses.push(Access::of::<T>(false));
+    }
+    fn required(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+}
+
+impl<'w, T: Component + 'static> QueryParam<'w> for &mut T {
+    type Item = Mut<'w, T>;
+    fn fetch(scene: &'w Scene, entity: &Entity) -> Option<Self::Item> {
+        scene.get_component_mut::<T>(entity)
+    }
+    fn access(accesses: &mut Vec<Access>) {
+        accesses.push(Access::of::<T>(true));
+    }
+    fn required(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+}
+
+impl<'w, T: Component + 'static> QueryParam<'w> for Option<&T> {
+    type Item = Option<AtomicRef<'w, T>>;
+    fn fetch(scene: &'w Scene, entity: &Entity) -> Option<Self::Item> {
+        Some(scene.component_storage.get_entity_component::<T>(entity))
+    }
+    fn access(accesses: &mut Vec<Access>) {
+        accesses.push(Access::of::<T>(false));
+    }
+}
+
+impl<'w, T: Component + 'static> QueryParam<'w> for Option<&mut T> {
+    type Item = Option<Mut<'w, T>>;
+    fn fetch(scene: &'w Scene, entity: &Entity) -> Option<Self::Item> {
+        Some(<&mut T as QueryParam<'w>>::fetch(scene, entity))
+    }
+    fn access(accesses: &mut Vec<Access>) {
+        accesses.push(Access::of::<T>(true));
+    }
+}
+
+macro_rules! impl_query_param_tuple {
+    ($($param:ident),*) => {
+        impl<'w, $($param: QueryParam<'w>),*> QueryParam<'w> for ($($param,)*) {
+            type Item = ($($param::Item,)*);
+            fn fetch(scene: &'w Scene, entity: &Entity) -> Option<Self::Item> {
+                Some(($($param::fetch(scene, entity)?,)*))
+            }
+            fn access(accesses: &mut Vec<Access>) {
+                $($param::access(accesses);)*
+            }
+            fn required(types: &mut Vec<TypeId>) {
+                $($param::required(types);)*
+            }
+        }
+    };
+}
+
+impl_query_param_tuple!(A);
+impl_query_param_tuple!(A, B);
+impl_query_param_tuple!(A, B, C);
+impl_query_param_tuple!(A, B, C, D);
+impl_query_param_tuple!(A, B, C, D, E);
+
+// Narrows down the entities a query visits without fetching anything
+pub trait QueryFilter {
+    fn matches(scene: &Scene, entity: &Entity, last_run: u32) -> bool;
+    // Same as QueryParam::required
+    fn required(_types: &mut Vec<TypeId>) {
+    }
+}
+
+pub struct With<T>(PhantomData<T>);
+pub struct Without<T>(PhantomData<T>);
+// Added or changed after the query's last run tick, see Scene::query_filtered_since
+pub struct Added<T>(PhantomData<T>);
+pub struct Changed<T>(PhantomData<T>);
+
+impl QueryFilter for () {
+    fn matches(_scene: &Scene, _entity: &Entity, _last_run: u32) -> bool {
+        true
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for With<T> {
+    fn matches(scene: &Scene, entity: &Entity, _last_run: u32) -> bool {
+        scene.component_storage.get_entity_component::<T>(entity).is_some()
+    }
+    fn required(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for Without<T> {
+    fn matches(scene: &Scene, entity: &Entity, _last_run: u32) -> bool {
+        scene.component_storage.get_entity_component::<T>(entity).is_none()
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for Added<T> {
+    fn matches(scene: &Scene, entity: &Entity, last_run: u32) -> bool {
+        scene.component_storage.get_component_ticks::<T>(entity).is_some_and(|ticks| ticks.is_added(last_run))
+    }
+    fn required(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for Changed<T> {
+    fn matches(scene: &Scene, entity: &Entity, last_run: u32) -> bool {
+        scene.component_storage.get_component_ticks::<T>(entity).is_some_and(|ticks| ticks.is_changed(last_run))
+    }
+    fn required(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+}
+
+macro_rules! impl_query_filter_tuple {
+    ($($filter:ident),*) => {
+        impl<$($filter: QueryFilter),*> QueryFilter for ($($filter,)*) {
+            fn matches(scene: &Scene, entity: &Entity, last_run: u32) -> bool {
+                $($filter::matches(scene, entity, last_run))&&*
+            }
+            fn required(types: &mut Vec<TypeId>) {
+                $($filter::required(types);)*
+            }
+        }
+    };
+}
+
+impl_query_filter_tuple!(A);
+impl_query_filter_tuple!(A, B);
+impl_query_filter_tuple!(A, B, C);
+impl_query_filter_tuple!(A, B, C, D);
+
+// Iterates the entities that have every required component and pass the filter
+pub struct Query<'w, Q: QueryParam<'w>, F: QueryFilter = ()> {
+    scene: &'w Scene,
+    // Added and Changed match changes made after this tick
+    last_run: u32,
+    // Owners of the smallest required storage, or every entity when nothing is required.
+    // Neither can change while the scene is borrowed
+    entities: Cow<'w, [Entity]>,
+    next_entity: usize,
+    _marker: PhantomData<(Q, F)>
+}
+
+impl<'w, Q: QueryParam<'w>, F: QueryFilter> Query<'w, Q, F> {
+    pub fn new(scene: &'w Scene, last_run: u32) -> Result<Self, QueryError> {
+        let mut accesses = vec![];
+        Q::access(&mut accesses);
+
+        // Same component twice where one of them is mutable would panic on the second borrow
+        for (i, access) in accesses.iter().enumerate() {
+            let is_conflicting = accesses[i + 1..].iter().any(|other| other.conflicts_with(access));
+            if is_conflicting {
+                return Err(QueryError::ConflictingAccess(access.type_name));
+            }
+        }
+
+        let mut required = vec![];
+        Q::required(&mut required);
+        F::required(&mut required);
+        let storage = &scene.component_storage;
+        let entities = match required.into_iter().min_by_key(|type_id| storage.count_by_type_id(*type_id)) {
+            Some(type_id) => Cow::Owned(storage.get_entities_by_type_id(type_id)),
+            None => Cow::Borrowed(storage.get_entities())
+        };
+
+        Ok(Self {
+            scene,
+            last_run,
+            entities,
+            next_entity: 0,
+            _marker: PhantomData
+        })
+    }
+}
+
+impl<'w, Q: QueryParam<'w>, F: QueryFilter> Iterator for Query<'w, Q, F> {
+    type Item = Q::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entity) = self.entities.get(self.next_entity).copied() {
+            self.next_entity += 1;
+
+            if !F::matches(self.scene, &entity, self.last_run) {
+                continue;
+            }
+            if let Some(item) = Q::fetch(self.scene, &entity) {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    ConflictingAccess(&'static str),
+    IndependentScript
+}
+
+impl Error for QueryError {}
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::ConflictingAccess(type_name) => write!(f, "Query borrows {} mutably together with another borrow of it", type_name),
+            QueryError::IndependentScript => write!(f, "Independent scripts can't query, they only reach their own entity")
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{error::Error, fmt::Display};
+
+use cgmath::{Vector3, Quaternion};
+use probable_spork_ecs::component::Entity;
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ReflectValue {
+    Bool(bool),
+    U32(u32),
+    Usize(usize),
+    F32(f32),
+    String(String),
+    Vec3([f32; 3]),
+    Quat([f32; 4]),
+    // Index and generation
+    Entity(u32, u32),
+    Struct(Vec<(String, ReflectValue)>)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub type_name: &'static str
+}
+
+#[derive(Debug)]
+pub enum ReflectError {
+    FieldNotFound(String),
+    TypeMismatch(String),
+    Json(serde_json::Error)
+}
+
+impl Error for ReflectError {}
+impl Display for ReflectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReflectError::FieldNotFound(field) => write!(f, "Field {} wasn't found", field),
+            ReflectError::TypeMismatch(field) => write!(f, "Value doesn't match the type of field {}", field),
+            ReflectError::Json(e) => write!(f, "Couldn't convert the value from/to JSON: {}", e)
+        }
+    }
+}
+
+// Implemented by #[derive(Reflect)] from script_gen_macro
+pub trait Reflect {
+    fn get_type_name(&self) -> &'static str;
+    fn get_fields(&self) -> Vec<FieldInfo>;
+    fn get_field(&self, name: &str) -> Option<ReflectValue>;
+    fn set_field(&mut self, name: &str, value: ReflectValue) -> Result<(), ReflectError>;
+
+    fn to_value(&self) -> ReflectValue {
+        let fields = self.get_fields().iter()
+            .filter_map(|field| self.get_field(field.name).map(|value| (field.name.to_string(), value)))
+            .collect();
+        ReflectValue::Struct(fields)
+    }
+
+    // Fields missing from the value keep their current state
+    fn apply_value(&mut self, value: ReflectValue) -> Result<(), ReflectError> {
+        match value {
+            ReflectValue::Struct(fields) => fields.into_iter()
+                .try_for_each(|(name, value)| self.set_field(&name, value)),
+            _ => Err(ReflectError::TypeMismatch(self.get_type_name().to_string()))
+        }
+    }
+
+    fn to_json(&self) -> Result<String, ReflectError> {
+        serde_json::to_string(&self.to_value()).map_err(ReflectError::Json)
+    }
+
+    fn apply_json(&mut self, json: &str) -> Result<(), ReflectError> {
+        let value = serde_json::from_str(json).map_err(ReflectError::Json)?;
+        self.apply_value(value)
+    }
+}
+
+// Field types a derived Reflect can expose
+pub trait ReflectField {
+    fn to_reflect_value(&self) -> ReflectValue;
+    // Returns false when the value has a different type
+    fn set_from_reflect_value(&mut self, value: ReflectValue) -> bool;
+}
+
+macro_rules! impl_reflect_field {
+    ($ty:ty, $variant:ident) => {
+        impl ReflectField for $ty {
+            fn to_reflect_value(&self) -> ReflectValue {
+                ReflectValue::$variant(self.clone())
+            }
+            fn set_from_reflect_value(&mut self, value: ReflectValue) -> bool {
+                match value {
+                    ReflectValue::$variant(value) => {
+                        *self = value;
+                        true
+                    },
+                    _ => false
+                }
+            }
+        }
+    };
+}
+
+impl_reflect_field!(bool, Bool);
+impl_reflect_field!(u32, U32);
+impl_reflect_field!(usize, Usize);
+impl_reflect_field!(f32, F32);
+impl_reflect_field!(String, String);
+
+impl ReflectField for Vector3<f32> {
+    fn to_reflect_value(&self) -> ReflectValue {
+        ReflectValue::Vec3((*self).into())
+    }
+    fn set_from_reflect_value(&mut self, value: ReflectValue) -> bool {
+        match value {
+            ReflectValue::Vec3(value) => {
+                *self = value.into();
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+impl ReflectField for Quaternion<f32> {
+    fn to_reflect_value(&self) -> ReflectValue {
+        ReflectValue::Quat([self.s, self.v.x, self.v.y, self.v.z])
+    }
+    fn set_from_reflect_value(&mut self, value: ReflectValue) -> bool {
+        match value {
+            ReflectValue::Quat([s, x, y, z]) => {
+                *self = Quaternion::new(s, x, y, z);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+impl ReflectField for Entity {
+    fn to_reflect_value(&self) -> ReflectValue {
+        ReflectValue::Entity(self.index(), self.generation())
+    }
+    fn set_from_reflect_value(&mut self, value: ReflectValue) -> bool {
+        match value {
+            ReflectValue::Entity(index, generation) => {
+                *self = Entity::new(index, generation);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+// Nested structs that derive Reflect
+impl<T: Reflect> ReflectField for T {
+    fn to_reflect_value(&self) -> ReflectValue {
+        self.to_value()
+    }
+    fn set_from_reflect_value(&mut self, value: ReflectValue) -> bool {
+        self.apply_value(value).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::entities::components::{Transform, MeshInstance};
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let mut mesh_instance = MeshInstance { mesh_index: 3, ..Default::default() };
+        mesh_instance.local_transform.position = Vector3::new(1.0, 2.0, 3.0);
+
+        let mut restored = MeshInstance::default();
+        restored.apply_json(&mesh_instance.to_json().unwrap()).unwrap();
+        assert_eq!(restored.mesh_index, 3);
+        assert_eq!(restored.local_transform, mesh_instance.local_transform);
+    }
+
+    #[test]
+    fn json_of_another_type_is_rejected() {
+        let mut transform = Transform::default();
+        let json = MeshInstance::default().to_json().unwrap();
+        assert!(matches!(transform.apply_json(&json), Err(ReflectError::FieldNotFound(_))));
+    }
+}
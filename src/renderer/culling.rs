@@ -0,0 +1,163 @@
+use std::ops::AddAssign;
+
+use cgmath::{Vector3, Vector4, Matrix, Matrix4, InnerSpace, Zero, EuclideanSpace, Transform};
+
+use crate::vertex::Vertex;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+
+        for vertex in vertices.iter() {
+            let [x, y, z] = vertex.position;
+            min = Vector3::new(min.x.min(x), min.y.min(y), min.z.min(z));
+            max = Vector3::new(max.x.max(x), max.y.max(y), max.z.max(z));
+        }
+
+        if vertices.is_empty() {
+            return Self { min: Vector3::zero(), max: Vector3::zero() };
+        }
+
+        Self { min, max }
+    }
+
+    pub fn get_corners(&self) -> [Vector3<f32>; 8] {
+        let Aabb { min, max } = self;
+        [
+            Vector3::new(min.x, min.y, min.z),
+            Vector3::new(max.x, min.y, min.z),
+            Vector3::new(min.x, max.y, min.z),
+            Vector3::new(max.x, max.y, min.z),
+            Vector3::new(min.x, min.y, max.z),
+            Vector3::new(max.x, min.y, max.z),
+            Vector3::new(min.x, max.y, max.z),
+            Vector3::new(max.x, max.y, max.z)
+        ]
+    }
+
+    pub fn to_sphere(&self) -> BoundingSphere {
+        let center = (self.min + self.max) * 0.5;
+        BoundingSphere {
+            center,
+            radius: (self.max - center).magnitude()
+        }
+    }
+
+    pub fn transform(&self, model: &Matrix4<f32>) -> Aabb {
+        let corners = self.get_corners().map(|corner| model.transform_point(cgmath::Point3::from_vec(corner)).to_vec());
+        let mut aabb = Aabb { min: corners[0], max: corners[0] };
+
+        for corner in corners.iter() {
+            aabb.min = Vector3::new(aabb.min.x.min(corner.x), aabb.min.y.min(corner.y), aabb.min.z.min(corner.z));
+            aabb.max = Vector3::new(aabb.max.x.max(corner.x), aabb.max.y.max(corner.y), aabb.max.z.max(corner.z));
+        }
+        aabb
+    }
+}
+
+impl BoundingSphere {
+    // Instances only carry a rotation and a translation, so the radius doesn't change
+    pub fn transform(&self, model: &Matrix4<f32>) -> BoundingSphere {
+        BoundingSphere {
+            center: model.transform_point(cgmath::Point3::from_vec(self.center)).to_vec(),
+            radius: self.radius
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far. xyz is the normal pointing inside, w the distance
+    planes: [Vector4<f32>; 6]
+}
+
+impl Default for Frustum {
+    // Zeroed planes let everything through
+    fn default() -> Self {
+        Self {
+            planes: [Vector4::zero(); 6]
+        }
+    }
+}
+
+impl Frustum {
+    // Gribb-Hartmann plane extraction, clip space depth is 0..1 like in wgpu
+    pub fn from_view_projection(view_projection: Matrix4<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i);
+
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2)
+        ].map(|plane| {
+            let normal_length = plane.truncate().magnitude();
+            if normal_length > 0.0 { plane / normal_length } else { plane }
+        });
+
+        Self { planes }
+    }
+
+    fn get_distance(plane: &Vector4<f32>, point: Vector3<f32>) -> f32 {
+        plane.truncate().dot(point) + plane.w
+    }
+
+    pub fn get_planes(&self) -> [[f32; 4]; 6] {
+        self.planes.map(|plane| plane.into())
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| Self::get_distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane normal
+            let positive_vertex = Vector3::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z }
+            );
+            Self::get_distance(plane, positive_vertex) >= 0.0
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CullingStats {
+    pub meshes: usize,
+    pub instances: usize,
+    // Stays equal to instances when the culling runs on the GPU
+    pub visible_instances: usize,
+    pub is_gpu_culled: bool
+}
+
+impl CullingStats {
+    pub fn get_culled_instances(&self) -> usize {
+        self.instances - self.visible_instances
+    }
+}
+
+impl AddAssign for CullingStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.meshes += rhs.meshes;
+        self.instances += rhs.instances;
+        self.visible_instances += rhs.visible_instances;
+        self.is_gpu_culled |= rhs.is_gpu_culled;
+    }
+}
@@ -1,5 +1,3 @@
-use std::cell::{RefCell, Ref};
-
 use egui::epaint::Primitive;
 use egui::{ClippedPrimitive, PaintCallbackInfo, TexturesDelta};
 use egui_wgpu::renderer::ScreenDescriptor;
@@ -8,13 +6,13 @@ use wgpu::{Color, RenderPass};
 use winit::window::Window;
 use crate::editor::GamePreviewCallback;
 
-use crate::entities::components::{MeshRenderer, MeshInstance};
-use crate::{WgpuStructs, renderer::Renderer, texture::Texture, RendererResources};
+use crate::entities::components::{MeshRenderer, MeshInstance, LodGroup, Transform};
+use crate::{WgpuStructs, renderer::Renderer, RendererResources};
 
-use super::{TransformInstance, MeshManager};
+use super::{TransformInstance, MeshManager, RenderTargets, RenderGraph, TexturePool};
 
 pub struct EditorRenderer {
-    depth_texture: Texture,
+    texture_pool: TexturePool,
     renderer: egui_wgpu::Renderer,
     screen_descriptor: ScreenDescriptor,
     clipped_primitives: Vec<ClippedPrimitive>,
@@ -29,19 +27,18 @@ impl EditorRenderer {
         texture_format: wgpu::TextureFormat, pixels_per_point: f32) -> Self {
         info!("Creating editor");
 
-        let WgpuStructs { device, config, .. } = wgpu_structs;
+        let WgpuStructs { device, sample_count, .. } = wgpu_structs;
 
-        let renderer = egui_wgpu::Renderer::new(device, texture_format, Some(crate::Texture::DEPTH_FORMAT), 1);
+        // The game preview is painted inside the egui pass, so both have to agree on the sample count
+        let renderer = egui_wgpu::Renderer::new(device, texture_format, Some(crate::texture::Texture::DEPTH_FORMAT), *sample_count);
 
         let screen_descriptor = ScreenDescriptor {
             pixels_per_point,
             size_in_pixels: window.inner_size().into()
         };
 
-        let depth_texture = Texture::create_depth_texture(&device, &config, "Depth texture");
-
         Self {
-            depth_texture,
+            texture_pool: TexturePool::new(),
             renderer,
             screen_descriptor,
             clipped_primitives: vec![],
@@ -61,7 +58,7 @@ impl EditorRenderer {
 
 
         for (id, image_delta) in self.textures_delta.set.iter() {
-            self.renderer.update_texture(device, queue, *id, &image_delta);
+            self.renderer.update_texture(device, queue, *id, image_delta);
         }
         self.renderer.update_buffers(device, queue, encoder, &self.clipped_primitives, &self.screen_descriptor);
     }
@@ -72,74 +69,68 @@ impl EditorRenderer {
             clip_rect: _,
             primitive
         } in clipped_primitive {
-            match primitive {
-                Primitive::Callback(callback) => {
-                    let cbfn = if let Some(c) = callback.callback.downcast_ref::<GamePreviewCallback>() {
-                        c
-                    } else {
-                        // We already warned in the `prepare` callback
-                        continue;
-                    };
-
-                    (cbfn.update)(
-                        device,
-                        queue,
-                        encoder,
-                        renderer_resources,
-                        &self.mesh_manager.get_meshes()
-                    );
-                },
-                _ => ()
+            if let Primitive::Callback(callback) = primitive {
+                let cbfn = if let Some(c) = callback.callback.downcast_ref::<GamePreviewCallback>() {
+                    c
+                } else {
+                    // We already warned in the `prepare` callback
+                    continue;
+                };
+
+                (cbfn.update)(
+                    device,
+                    queue,
+                    encoder,
+                    renderer_resources,
+                    &self.mesh_manager
+                );
             }
         }
     }
 
 
-    fn call_game_preview_render<'a>(&'a self, render_pass: &mut RenderPass<'a>, clipped_primitive: &Vec<ClippedPrimitive>, 
-        renderer_resources: &'a RendererResources) {
+    fn call_game_preview_render<'a>(render_pass: &mut RenderPass<'a>, clipped_primitive: &Vec<ClippedPrimitive>, 
+        screen_descriptor: &ScreenDescriptor, mesh_manager: &'a MeshManager, renderer_resources: &'a RendererResources) {
         for egui::epaint::ClippedPrimitive {
             clip_rect,
             primitive
         } in clipped_primitive {
-            match primitive {
-                Primitive::Callback(callback) => {
-                    let cbfn = if let Some(c) = callback.callback.downcast_ref::<GamePreviewCallback>() {
-                        c
-                    } else {
-                        continue;
-                    };
-
-                    let pixels_per_point = self.screen_descriptor.pixels_per_point;
-
-                    {
-
-                        let min = (callback.rect.min.to_vec2() * pixels_per_point).round();
-                        let max = (callback.rect.max.to_vec2() * pixels_per_point).round();
-
-                        render_pass.set_viewport(
-                            min.x,
-                            min.y,
-                            max.x - min.x,
-                            max.y - min.y,
-                            0.0,
-                            1.0,
-                        );
-                    }
-
-                    (cbfn.render)(
-                        PaintCallbackInfo {
-                            viewport: callback.rect,
-                            clip_rect: *clip_rect,
-
-                            pixels_per_point,
-                            screen_size_px: self.screen_descriptor.size_in_pixels,
-                        },
-                        render_pass,
-                        renderer_resources,
-                        &self.mesh_manager.get_meshes()
+            if let Primitive::Callback(callback) = primitive {
+                let cbfn = if let Some(c) = callback.callback.downcast_ref::<GamePreviewCallback>() {
+                    c
+                } else {
+                    continue;
+                };
+
+                let pixels_per_point = screen_descriptor.pixels_per_point;
+
+                {
+
+                    let min = (callback.rect.min.to_vec2() * pixels_per_point).round();
+                    let max = (callback.rect.max.to_vec2() * pixels_per_point).round();
+
+                    render_pass.set_viewport(
+                        min.x,
+                        min.y,
+                        max.x - min.x,
+                        max.y - min.y,
+                        0.0,
+                        1.0,
                     );
-                },
-                _ => ()
+                }
+
+                (cbfn.render)(
+                    PaintCallbackInfo {
+                        viewport: callback.rect,
+                        clip_rect: *clip_rect,
+
+                        pixels_per_point,
+                        screen_size_px: screen_descriptor.size_in_pixels,
+                    },
+                    render_pass,
+                    renderer_resources,
+                    mesh_manager
+                );
             }
         }
     }
@@ -154,7 +145,7 @@ impl Renderer for EditorRenderer {
         &mut self.mesh_manager
     }
 
-    fn update_meshes(&mut self, mesh_instances: Vec<MeshInstance>) {
+    fn update_meshes(&mut self, mesh_instances: Vec<MeshInstance>, renderer_resources: &mut RendererResources) {
         for mesh_instance in mesh_instances.iter() {
             match self.mesh_manager.get_meshes_mut().get_mut(mesh_instance.mesh_index) {
                 Some(mesh) => {
@@ -167,14 +158,15 @@ impl Renderer for EditorRenderer {
                 }
             }
         }
+
+        renderer_resources.culling_stats = self.mesh_manager.cull_instances(&renderer_resources.frustum);
     }
 
-    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>, scale_factor: Option<f32>, depth_texture: Option<Texture>) {
-        match depth_texture {
-            Some(depth_texture) => self.depth_texture = depth_texture,
-            None => ()
-        }
+    fn update_lod_groups(&mut self, lod_groups: Vec<(LodGroup, Transform)>, renderer_resources: &RendererResources) {
+        self.mesh_manager.update_lod_groups(lod_groups, renderer_resources.camera_position, renderer_resources.camera_fovy);
+    }
 
+    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>, scale_factor: Option<f32>) {
         self.screen_descriptor.size_in_pixels = size.into();
 
         if let Some(pixels_per_point) = scale_factor {
@@ -194,35 +186,32 @@ impl Renderer for EditorRenderer {
 
         //renderer_resources.renderables.iter().for_each(|renderable| renderable.update_instance_data(queue));
 
+        self.mesh_manager.reserve_instance_buffers(device);
         self.update_ui_textures(device, queue, &mut encoder, window);
         self.call_game_preview_update(device, queue, &mut encoder, &self.clipped_primitives, renderer_resources);
-        {
+
+        let mut graph = RenderGraph::new();
+        let render_targets = RenderTargets::declare(&mut graph, &view, wgpu_structs);
+        let (renderer, clipped_primitives, screen_descriptor, mesh_manager) =
+            (&self.renderer, &self.clipped_primitives, &self.screen_descriptor, &self.mesh_manager);
+
+        graph.add_pass("Editor render pass", &[], &render_targets.get_writes(), |encoder, resources| {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Editor render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment { 
-                    view: &view, 
-                    resolve_target: None, 
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0
-                        }),
-                        store: true
-                    }
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true
-                    }),
-                    stencil_ops: None
-                })
+                color_attachments: &[Some(render_targets.color_attachment(resources, Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0
+                }))],
+                depth_stencil_attachment: Some(render_targets.depth_stencil_attachment(resources))
             });
-            self.renderer.render(&mut render_pass, &self.clipped_primitives, &self.screen_descriptor);
-            self.call_game_preview_render(&mut render_pass, &self.clipped_primitives, renderer_resources);
+            renderer.render(&mut render_pass, clipped_primitives, screen_descriptor);
+            Self::call_game_preview_render(&mut render_pass, clipped_primitives, screen_descriptor, mesh_manager, renderer_resources);
+        });
+
+        if let Err(e) = graph.execute(device, &mut encoder, &mut self.texture_pool) {
+            warn!("Couldn't execute render graph: {}", e);
         }
 
         queue.submit(std::iter::once(encoder.finish()));
@@ -0,0 +1,55 @@
+use crate::{entities::{CameraUniform, components::{MeshInstance, LodGroup, Transform}}, scene::Scene, RendererResources};
+
+use super::Frustum;
+
+// RenderExtract systems write into these scene resources instead of RendererResources, so they can
+// run in the same batch. Engine::update moves them over once the schedule is done
+pub struct ExtractedCamera {
+    pub camera_uniform: CameraUniform,
+    pub frustum: Frustum,
+    pub position: cgmath::Point3<f32>,
+    pub fovy: cgmath::Deg<f32>
+}
+
+impl Default for ExtractedCamera {
+    fn default() -> Self {
+        Self {
+            camera_uniform: CameraUniform::new(),
+            frustum: Frustum::default(),
+            position: cgmath::Point3::new(0.0, 0.0, 0.0),
+            fovy: cgmath::Deg(45.0)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ExtractedMeshInstances {
+    pub mesh_instances: Vec<MeshInstance>,
+    pub removed_mesh_instances: Vec<MeshInstance>
+}
+
+#[derive(Default)]
+pub struct ExtractedLodGroups(pub Vec<(LodGroup, Transform)>);
+
+pub fn insert_extract_resources(scene: &mut Scene) {
+    scene.insert_resource(ExtractedCamera::default());
+    scene.insert_resource(ExtractedMeshInstances::default());
+    scene.insert_resource(ExtractedLodGroups::default());
+}
+
+// The camera is copied, so it stays valid on frames where the extract system doesn't run
+pub fn move_extracted(scene: &Scene, renderer_resources: &mut RendererResources) {
+    if let Some(camera) = scene.resource::<ExtractedCamera>() {
+        renderer_resources.camera_uniform = camera.camera_uniform;
+        renderer_resources.frustum = camera.frustum;
+        renderer_resources.camera_position = camera.position;
+        renderer_resources.camera_fovy = camera.fovy;
+    }
+    if let Some(mut extracted) = scene.resource_mut::<ExtractedMeshInstances>() {
+        renderer_resources.mesh_instances = std::mem::take(&mut extracted.mesh_instances);
+        renderer_resources.removed_mesh_instances = std::mem::take(&mut extracted.removed_mesh_instances);
+    }
+    if let Some(mut extracted) = scene.resource_mut::<ExtractedLodGroups>() {
+        renderer_resources.lod_groups = std::mem::take(&mut extracted.0);
+    }
+}
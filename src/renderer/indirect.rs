@@ -0,0 +1,303 @@
+use std::cell::Cell;
+
+use log::{info, warn};
+
+use crate::{entities::components::MeshRenderer, shader::load_shader_module, vertex::Vertex};
+
+use super::{Frustum, CullingStats, transform_instance::TransformInstanceRaw};
+
+const MAX_INDIRECT_MESHES: usize = 256;
+const MAX_INDIRECT_INSTANCES: usize = 16384;
+const MAX_INDIRECT_VERTICES: usize = 1 << 20;
+const MAX_INDIRECT_INDICES: usize = 1 << 20;
+const CULL_WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniform {
+    planes: [[f32; 4]; 6],
+    instance_count: u32,
+    _padding: [u32; 3]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuInstance {
+    model: [[f32; 4]; 4],
+    bounding_sphere: [f32; 4],
+    fade: f32,
+    mesh_index: u32,
+    _padding: [u32; 2]
+}
+
+// Same layout wgpu expects in indirect buffers
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32
+}
+
+struct MeshRange {
+    first_index: u32,
+    index_count: u32,
+    base_vertex: i32
+}
+
+// Consecutive meshes sharing a material, drawn with one multi draw call
+struct DrawBatch {
+    first_mesh: usize,
+    mesh_count: usize
+}
+
+// Optional GPU driven path. All meshes are packed into shared vertex/index buffers,
+// a compute shader culls every instance and fills the DrawIndexedIndirect arguments
+pub struct IndirectDrawer {
+    cull_pipeline: wgpu::ComputePipeline,
+    cull_bind_group: wgpu::BindGroup,
+    cull_uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    instance_offset_buffer: wgpu::Buffer,
+    draw_args_buffer: wgpu::Buffer,
+    visible_instance_buffer: wgpu::Buffer,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    supports_multi_draw: bool,
+    packed_mesh_count: usize,
+    mesh_ranges: Vec<MeshRange>,
+    batches: Vec<DrawBatch>,
+    packed_vertices: Vec<Vertex>,
+    packed_indices: Vec<u16>,
+    is_geometry_uploaded: Cell<bool>,
+    cull_uniform: CullUniform,
+    instances: Vec<GpuInstance>,
+    instance_offsets: Vec<u32>,
+    instance_counts: Vec<u32>,
+    draw_args: Vec<DrawIndexedIndirect>
+}
+
+impl IndirectDrawer {
+    pub fn new(device: &wgpu::Device) -> Result<Self, anyhow::Error> {
+        // Without first_instance every draw starts at instance 0, so the instance buffer has to be rebound per mesh
+        let supports_multi_draw = device.features()
+            .contains(wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE);
+        info!("Indirect drawing, multi draw supported: {}", supports_multi_draw);
+
+        let shader = load_shader_module(device, "indirect_cull.wgsl")?;
+        let cull_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Indirect cull pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "cs_main"
+        });
+
+        let create_buffer = |label: &str, size: usize, usage: wgpu::BufferUsages| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as wgpu::BufferAddress,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        let cull_uniform_buffer = create_buffer("Indirect cull uniform buffer", std::mem::size_of::<CullUniform>(), wgpu::BufferUsages::UNIFORM);
+        let instance_buffer = create_buffer("Indirect instance buffer",
+            MAX_INDIRECT_INSTANCES * std::mem::size_of::<GpuInstance>(), wgpu::BufferUsages::STORAGE);
+        let instance_offset_buffer = create_buffer("Indirect instance offset buffer",
+            MAX_INDIRECT_MESHES * std::mem::size_of::<u32>(), wgpu::BufferUsages::STORAGE);
+        let draw_args_buffer = create_buffer("Indirect draw args buffer",
+            MAX_INDIRECT_MESHES * std::mem::size_of::<DrawIndexedIndirect>(), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT);
+        let visible_instance_buffer = create_buffer("Indirect visible instance buffer",
+            MAX_INDIRECT_INSTANCES * std::mem::size_of::<TransformInstanceRaw>(), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX);
+        let vertex_buffer = create_buffer("Indirect vertex buffer",
+            MAX_INDIRECT_VERTICES * std::mem::size_of::<Vertex>(), wgpu::BufferUsages::VERTEX);
+        let index_buffer = create_buffer("Indirect index buffer",
+            MAX_INDIRECT_INDICES * std::mem::size_of::<u16>(), wgpu::BufferUsages::INDEX);
+
+        let cull_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Indirect cull bind group"),
+            layout: &cull_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: cull_uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: instance_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: instance_offset_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: draw_args_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: visible_instance_buffer.as_entire_binding() }
+            ]
+        });
+
+        Ok(Self {
+            cull_pipeline,
+            cull_bind_group,
+            cull_uniform_buffer,
+            instance_buffer,
+            instance_offset_buffer,
+            draw_args_buffer,
+            visible_instance_buffer,
+            vertex_buffer,
+            index_buffer,
+            supports_multi_draw,
+            packed_mesh_count: 0,
+            mesh_ranges: vec![],
+            batches: vec![],
+            packed_vertices: vec![],
+            packed_indices: vec![],
+            is_geometry_uploaded: Cell::new(false),
+            cull_uniform: CullUniform {
+                planes: Frustum::default().get_planes(),
+                instance_count: 0,
+                _padding: [0; 3]
+            },
+            instances: vec![],
+            instance_offsets: vec![],
+            instance_counts: vec![],
+            draw_args: vec![]
+        })
+    }
+
+    fn pack_geometry(&mut self, meshes: &[Box<dyn MeshRenderer>]) {
+        self.mesh_ranges.clear();
+        self.batches.clear();
+        self.packed_vertices.clear();
+        self.packed_indices.clear();
+
+        for (mesh_index, mesh) in meshes.iter().enumerate() {
+            let (vertices, indices) = (mesh.get_vertices(), mesh.get_indices());
+            if mesh_index >= MAX_INDIRECT_MESHES
+                || self.packed_vertices.len() + vertices.len() > MAX_INDIRECT_VERTICES
+                || self.packed_indices.len() + indices.len() > MAX_INDIRECT_INDICES {
+                warn!("Indirect buffers are full, only the first {} meshes are drawn", mesh_index);
+                break;
+            }
+
+            self.mesh_ranges.push(MeshRange {
+                first_index: self.packed_indices.len() as u32,
+                index_count: indices.len() as u32,
+                base_vertex: self.packed_vertices.len() as i32
+            });
+            self.packed_vertices.extend_from_slice(vertices);
+            self.packed_indices.extend_from_slice(indices);
+
+            let shares_material = mesh_index > 0 && meshes[mesh_index - 1].get_material_id() == mesh.get_material_id();
+            match self.batches.last_mut() {
+                Some(batch) if shares_material => batch.mesh_count += 1,
+                _ => self.batches.push(DrawBatch { first_mesh: mesh_index, mesh_count: 1 })
+            }
+        }
+
+        // Buffer writes have to be a multiple of 4 bytes
+        if !self.packed_indices.len().is_multiple_of(2) {
+            self.packed_indices.push(0);
+        }
+        self.packed_mesh_count = meshes.len();
+        self.is_geometry_uploaded.set(false);
+    }
+
+    pub fn prepare(&mut self, meshes: &[Box<dyn MeshRenderer>], frustum: &Frustum) -> CullingStats {
+        if self.packed_mesh_count != meshes.len() {
+            self.pack_geometry(meshes);
+        }
+
+        self.instances.clear();
+        self.instance_offsets.clear();
+        self.instance_counts.clear();
+        self.draw_args.clear();
+
+        for (mesh_index, (mesh, range)) in meshes.iter().zip(self.mesh_ranges.iter()).enumerate() {
+            let first_instance = self.instances.len() as u32;
+            let sphere = mesh.get_bounding_sphere();
+
+            for instance in mesh.get_instances().iter() {
+                if self.instances.len() >= MAX_INDIRECT_INSTANCES {
+                    warn!("Indirect instance buffer is full");
+                    break;
+                }
+
+                self.instances.push(GpuInstance {
+                    model: instance.to_matrix().into(),
+                    bounding_sphere: [sphere.center.x, sphere.center.y, sphere.center.z, sphere.radius],
+                    fade: instance.fade,
+                    mesh_index: mesh_index as u32,
+                    _padding: [0; 2]
+                });
+            }
+
+            self.instance_offsets.push(first_instance);
+            self.instance_counts.push(self.instances.len() as u32 - first_instance);
+            self.draw_args.push(DrawIndexedIndirect {
+                index_count: range.index_count,
+                // Filled in by the cull shader
+                instance_count: 0,
+                first_index: range.first_index,
+                base_vertex: range.base_vertex,
+                first_instance: if self.supports_multi_draw { first_instance } else { 0 }
+            });
+        }
+
+        self.cull_uniform.planes = frustum.get_planes();
+        self.cull_uniform.instance_count = self.instances.len() as u32;
+
+        CullingStats {
+            meshes: self.mesh_ranges.len(),
+            instances: self.instances.len(),
+            visible_instances: self.instances.len(),
+            is_gpu_culled: true
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        if !self.is_geometry_uploaded.get() {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.packed_vertices));
+            queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&self.packed_indices));
+            self.is_geometry_uploaded.set(true);
+        }
+
+        if self.instances.is_empty() {
+            return;
+        }
+
+        queue.write_buffer(&self.cull_uniform_buffer, 0, bytemuck::cast_slice(&[self.cull_uniform]));
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+        queue.write_buffer(&self.instance_offset_buffer, 0, bytemuck::cast_slice(&self.instance_offsets));
+        queue.write_buffer(&self.draw_args_buffer, 0, bytemuck::cast_slice(&self.draw_args));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Indirect cull pass")
+        });
+        compute_pass.set_pipeline(&self.cull_pipeline);
+        compute_pass.set_bind_group(0, &self.cull_bind_group, &[]);
+
+        let workgroups = (self.instances.len() as u32).div_ceil(CULL_WORKGROUP_SIZE);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, meshes: &'a [Box<dyn MeshRenderer>]) {
+        let draw_args_size = std::mem::size_of::<DrawIndexedIndirect>() as wgpu::BufferAddress;
+        let instance_size = std::mem::size_of::<TransformInstanceRaw>() as wgpu::BufferAddress;
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        for batch in self.batches.iter() {
+            meshes[batch.first_mesh].set_material(render_pass);
+
+            if self.supports_multi_draw {
+                render_pass.set_vertex_buffer(1, self.visible_instance_buffer.slice(..));
+                render_pass.multi_draw_indexed_indirect(&self.draw_args_buffer, batch.first_mesh as wgpu::BufferAddress * draw_args_size,
+                    batch.mesh_count as u32);
+                continue;
+            }
+
+            for mesh_index in batch.first_mesh..batch.first_mesh + batch.mesh_count {
+                if self.instance_counts.get(mesh_index).is_none_or(|count| *count == 0) {
+                    continue;
+                }
+
+                let instance_offset = self.instance_offsets[mesh_index] as wgpu::BufferAddress * instance_size;
+                render_pass.set_vertex_buffer(1, self.visible_instance_buffer.slice(instance_offset..));
+                render_pass.draw_indexed_indirect(&self.draw_args_buffer, mesh_index as wgpu::BufferAddress * draw_args_size);
+            }
+        }
+    }
+}
@@ -1,27 +1,31 @@
-use std::cell::Ref;
-
 use log::warn;
 
-use crate::{renderer::Renderer, WgpuStructs, RendererResources, texture::Texture};
-use crate::entities::components::{MeshRenderer, MeshInstance};
+use crate::{renderer::Renderer, WgpuStructs, RendererResources};
+use crate::entities::components::{MeshRenderer, MeshInstance, LodGroup, Transform};
 
-use super::{TransformInstance, MeshManager};
-use super::renderer::RendererLoop;
+use super::{TransformInstance, MeshManager, RenderTargets, RenderGraph, TexturePool};
+use super::renderer_loop::RendererLoop;
 
 pub struct MainRenderer {
-    depth_texture: Texture,
+    texture_pool: TexturePool,
     mesh_manager: MeshManager
 }
 
 impl MainRenderer {
-    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    pub fn new() -> Self {
         Self {
-            depth_texture: Texture::create_depth_texture(device, config, "Depth texture"),
+            texture_pool: TexturePool::new(),
             mesh_manager: MeshManager::new()
         }
     }
 }
 
+impl Default for MainRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Renderer for MainRenderer {
     fn get_mesh_manager(&self) -> &MeshManager {
         &self.mesh_manager
@@ -31,7 +35,7 @@ impl Renderer for MainRenderer {
         &mut self.mesh_manager
     }
 
-    fn update_meshes(&mut self, mesh_instances: Vec<MeshInstance>) {
+    fn update_meshes(&mut self, mesh_instances: Vec<MeshInstance>, renderer_resources: &mut RendererResources) {
         for mesh_instance in mesh_instances.iter() {
             match self.mesh_manager.get_meshes_mut().get_mut(mesh_instance.mesh_index) {
                 Some(mesh) => {
@@ -44,18 +48,18 @@ impl Renderer for MainRenderer {
                 }
             }
         }
+
+        renderer_resources.culling_stats = self.mesh_manager.cull_instances(&renderer_resources.frustum);
     }
 
-    fn resize(&mut self, _new_size: winit::dpi::PhysicalSize<u32>, _scale_factor: Option<f32>, depth_texture: Option<Texture>) {
-        match depth_texture {
-            Some(depth_texture) => self.depth_texture = depth_texture,
-            None => ()
-        }
+    fn update_lod_groups(&mut self, lod_groups: Vec<(LodGroup, Transform)>, renderer_resources: &RendererResources) {
+        self.mesh_manager.update_lod_groups(lod_groups, renderer_resources.camera_position, renderer_resources.camera_fovy);
     }
 
-    fn render<'a>(&'a mut self, wgpu_structs: &WgpuStructs, _window: &winit::window::Window, renderer_resources: &mut RendererResources) -> Result<(), wgpu::SurfaceError> {
-        RendererLoop::update(&wgpu_structs.queue, renderer_resources, &self.mesh_manager.get_meshes());
+    fn resize(&mut self, _new_size: winit::dpi::PhysicalSize<u32>, _scale_factor: Option<f32>) {
+    }
 
+    fn render(&mut self, wgpu_structs: &WgpuStructs, _window: &winit::window::Window, renderer_resources: &mut RendererResources) -> Result<(), wgpu::SurfaceError> {
         let WgpuStructs { surface, device, queue, .. } = wgpu_structs;
         let output = surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -63,34 +67,30 @@ impl Renderer for MainRenderer {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder")
         });
+        self.mesh_manager.reserve_instance_buffers(device);
+        RendererLoop::update(queue, &mut encoder, renderer_resources, &self.mesh_manager);
+
+        let mut graph = RenderGraph::new();
+        let render_targets = RenderTargets::declare(&mut graph, &view, wgpu_structs);
+        let mesh_manager = &self.mesh_manager;
 
-        {
+        graph.add_pass("Render pass", &[], &render_targets.get_writes(), |encoder, resources| {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment { 
-                    view: &view, 
-                    resolve_target: None, 
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0
-                        }),
-                        store: true
-                    }
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true
-                    }),
-                    stencil_ops: None
-                })
+                color_attachments: &[Some(render_targets.color_attachment(resources, wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0
+                }))],
+                depth_stencil_attachment: Some(render_targets.depth_stencil_attachment(resources))
             });
 
-            RendererLoop::render(&mut render_pass, renderer_resources, &self.mesh_manager.get_meshes());
+            RendererLoop::render(&mut render_pass, renderer_resources, mesh_manager);
+        });
+
+        if let Err(e) = graph.execute(device, &mut encoder, &mut self.texture_pool) {
+            warn!("Couldn't execute render graph: {}", e);
         }
 
         queue.submit(std::iter::once(encoder.finish()));
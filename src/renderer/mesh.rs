@@ -1,45 +1,92 @@
 use std::sync::Arc;
 
-use cgmath::{Vector3, Quaternion, Rotation3};
-
 use wgpu::util::DeviceExt;
 use crate::entities::CameraUniform;
 use crate::{vertex::Vertex, shader::{Shader, BIND_GROUP_POSTFIX}, texture::Texture};
 use crate::entities::components::{MeshRenderer, MeshRendererError};
 
-use super::TransformInstance;
+use super::{TransformInstance, Aabb, BoundingSphere, Frustum, CullingStats};
 use super::transform_instance::TransformInstanceRaw;
 
+// Instances the instance buffer holds before it first has to grow
+const INITIAL_INSTANCE_CAPACITY: usize = 20;
+
 pub struct TexturedMesh {
     pub label: String,
     pub shader: Arc<Shader>,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
-    pub texture: Texture,
+    pub texture: Arc<Texture>,
     pub texture_bind_group: wgpu::BindGroup,
     pub camera_bind_group: wgpu::BindGroup,
     pub instance_buffer: wgpu::Buffer,
-    pub instances: Vec<TransformInstance>
+    // In instances
+    instance_capacity: usize,
+    pub instances: Vec<TransformInstance>,
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+    // Removed instance slots, reused by create_instance
+    free_instances: Vec<usize>,
+    // Instances updated since the last cull, only these get uploaded again
+    changed_instances: Vec<bool>,
+    visible_instances: Vec<usize>,
+    // Offset in the instance buffer (in instances) and the data to write there this frame
+    pending_instance_writes: Vec<(usize, Vec<TransformInstanceRaw>)>
 }
 
 impl MeshRenderer for TexturedMesh {
     fn create_instance(&mut self) -> usize {
+        if let Some(instance_index) = self.free_instances.pop() {
+            self.instances[instance_index] = TransformInstance::default();
+            self.changed_instances[instance_index] = true;
+            return instance_index;
+        }
+
         let instance_index = self.instances.len();
         self.instances.push(TransformInstance::default());
+        self.changed_instances.push(true);
 
         instance_index
     }
 
+    fn remove_instance(&mut self, instance_index: usize) -> Result<(), MeshRendererError> {
+        if self.free_instances.contains(&instance_index) {
+            return Err(MeshRendererError::InstanceNotFound);
+        }
+
+        match self.instances.get_mut(instance_index) {
+            Some(instance) => {
+                // Hidden instances are skipped by culling, so the slot stops being drawn
+                instance.fade = 0.0;
+                self.changed_instances[instance_index] = true;
+                self.free_instances.push(instance_index);
+                Ok(())
+            },
+            None => Err(MeshRendererError::InstanceNotFound)
+        }
+    }
+
     fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
-        render_pass.set_pipeline(&self.shader.render_pipeline);
+        self.set_material(render_pass);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        render_pass.draw_indexed(0..self.index_count, 0, 0..self.visible_instances.len() as u32);
+    }
+
+    fn set_material<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.shader.render_pipeline);
         render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
         render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+    }
 
-        render_pass.draw_indexed(0..self.index_count, 0, 0..self.instances.len() as u32);
+    // Every mesh has its own bind group, but the texture behind it can be shared
+    fn get_material_id(&self) -> (usize, usize) {
+        (Arc::as_ptr(&self.shader) as usize, Arc::as_ptr(&self.texture) as usize)
     }
 
     fn update_camera(&self, queue: &wgpu::Queue, camera_uniform_slice: &[CameraUniform]) {
@@ -52,25 +99,88 @@ impl MeshRenderer for TexturedMesh {
     }
 
     fn update_instance_data(&mut self, instance_index: usize, transform: TransformInstance) -> Result<(), MeshRendererError> {
+        if self.free_instances.contains(&instance_index) {
+            return Err(MeshRendererError::InstanceNotFound);
+        }
+
         match self.instances.get_mut(instance_index) {
             Some(instance) => {
-                *instance = transform.clone();
+                if *instance != transform {
+                    *instance = transform;
+                    self.changed_instances[instance_index] = true;
+                }
                 Ok(())
             },
             None => Err(MeshRendererError::InstanceNotFound)
         }
     }
 
+    fn reserve_instance_buffer(&mut self, device: &wgpu::Device) {
+        if self.visible_instances.len() <= self.instance_capacity {
+            return;
+        }
+
+        self.instance_capacity = self.visible_instances.len().max(self.instance_capacity * 2);
+        self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        // The new buffer is empty, so every visible instance gets written
+        self.pending_instance_writes = vec![(0, self.visible_instances.iter()
+            .map(|instance_index| TransformInstanceRaw::from(&self.instances[*instance_index]))
+            .collect())];
+    }
+
     fn write_instance_data(&self, queue: &wgpu::Queue) {
-        let instance_data: Vec<TransformInstanceRaw> = self.instances.iter().map(TransformInstanceRaw::from).collect();
-        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instance_data));
+        let instance_size = std::mem::size_of::<TransformInstanceRaw>();
+        for (offset, instance_data) in self.pending_instance_writes.iter() {
+            queue.write_buffer(&self.instance_buffer, (offset * instance_size) as wgpu::BufferAddress, bytemuck::cast_slice(instance_data));
+        }
     }
 
+    fn cull_instances(&mut self, frustum: &Frustum) -> CullingStats {
+        let Self { instances, bounds, bounding_sphere, .. } = self;
+
+        let visible_instances: Vec<usize> = instances.iter()
+            .enumerate()
+            .filter(|(_, instance)| instance.fade != 0.0)
+            .filter(|(_, instance)| {
+                let model = instance.to_matrix();
+                // Sphere test is cheaper, the box only gets checked for what passes it
+                frustum.intersects_sphere(&bounding_sphere.transform(&model)) && frustum.intersects_aabb(&bounds.transform(&model))
+            })
+            .map(|(instance_index, _)| instance_index)
+            .collect();
+
+        self.pending_instance_writes = self.get_instance_writes(&visible_instances);
+        self.visible_instances = visible_instances;
+        self.changed_instances.iter_mut().for_each(|is_changed| *is_changed = false);
+
+        CullingStats {
+            meshes: 1,
+            instances: self.instances.len(),
+            visible_instances: self.visible_instances.len(),
+            is_gpu_culled: false
+        }
+    }
+
+    fn get_bounding_sphere(&self) -> &BoundingSphere {
+        &self.bounding_sphere
+    }
+
+    fn get_vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    fn get_indices(&self) -> &[u16] {
+        &self.indices
+    }
+
+    fn get_instances(&self) -> &[TransformInstance] {
+        &self.instances
+    }
 }
 
 impl TexturedMesh {
     pub fn from(label: String, device: &wgpu::Device, vertices: &[Vertex], indices: &[u16], shader: Arc<Shader>,
-        texture: Texture) -> Result<TexturedMesh, anyhow::Error> {
+        texture: Arc<Texture>) -> Result<TexturedMesh, anyhow::Error> {
         let vertex_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex buffer"),
@@ -87,12 +197,9 @@ impl TexturedMesh {
             }
         );
 
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Instance buffer"),
-            size: (20 * std::mem::size_of::<[[f32; 4]; 4]>()) as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false
-        });
+        let instance_buffer = Self::create_instance_buffer(device, INITIAL_INSTANCE_CAPACITY);
+
+        let bounds = Aabb::from_vertices(vertices);
 
         let texture_uniform = shader.get_uniform("texture")?;
 
@@ -101,7 +208,7 @@ impl TexturedMesh {
 
         //TODO - Recreate camera buffer if it doesn't exist
         let camera_buffer = camera_uniform.buffer.as_ref().expect("camera_uniform doesn't have buffer");
-        let camera_bind_group = Self::create_camera_bind_group(device, "camera", &camera_uniform.layout, &camera_buffer);
+        let camera_bind_group = Self::create_camera_bind_group(device, "camera", &camera_uniform.layout, camera_buffer);
 
         Ok(Self {
             label,
@@ -113,14 +220,55 @@ impl TexturedMesh {
             texture_bind_group,
             camera_bind_group,
             instance_buffer,
-            instances: vec![]
+            instance_capacity: INITIAL_INSTANCE_CAPACITY,
+            instances: vec![],
+            bounds,
+            bounding_sphere: bounds.to_sphere(),
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+            free_instances: vec![],
+            changed_instances: vec![],
+            visible_instances: vec![],
+            pending_instance_writes: vec![]
         })
     }
 
 
+    // The buffer only holds visible instances, so it's rewritten whole when that set changes.
+    // Otherwise only runs of changed instances are written
+    fn get_instance_writes(&self, visible_instances: &Vec<usize>) -> Vec<(usize, Vec<TransformInstanceRaw>)> {
+        let to_raw = |instance_index: &usize| TransformInstanceRaw::from(&self.instances[*instance_index]);
+
+        if *visible_instances != self.visible_instances {
+            return vec![(0, visible_instances.iter().map(to_raw).collect())];
+        }
+
+        let mut writes: Vec<(usize, Vec<TransformInstanceRaw>)> = vec![];
+        for (position, instance_index) in visible_instances.iter().enumerate() {
+            if !self.changed_instances[*instance_index] {
+                continue;
+            }
+
+            match writes.last_mut() {
+                Some((offset, instance_data)) if *offset + instance_data.len() == position => instance_data.push(to_raw(instance_index)),
+                _ => writes.push((position, vec![to_raw(instance_index)]))
+            }
+        }
+        writes
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance buffer"),
+            size: (capacity * std::mem::size_of::<TransformInstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        })
+    }
+
     fn create_camera_bind_group(device: &wgpu::Device, label: &str, layout: &wgpu::BindGroupLayout, camera_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
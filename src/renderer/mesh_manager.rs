@@ -1,20 +1,47 @@
-use log::warn;
+use cgmath::{InnerSpace, EuclideanSpace};
+use log::{info, warn};
 
-use crate::entities::components::MeshRenderer;
+use crate::entities::components::{MeshRenderer, LodGroup, LodThreshold, Transform};
+
+use crate::WgpuStructs;
+
+use super::{Frustum, CullingStats, TransformInstance, IndirectDrawer};
 
 pub struct MeshManager {
-    meshes: Vec<Box<dyn MeshRenderer>>
+    meshes: Vec<Box<dyn MeshRenderer>>,
+    indirect_drawer: Option<IndirectDrawer>
 }
 
 impl MeshManager {
     pub fn new() -> Self {
         Self {
-            meshes: vec![]
+            meshes: vec![],
+            indirect_drawer: None
+        }
+    }
+
+    // Moves culling and draw submission to the GPU, stays on the per mesh path if the device can't do it
+    pub fn enable_indirect_drawing(&mut self, wgpu_structs: &WgpuStructs) {
+        let required_flags = wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION;
+        if !wgpu_structs.downlevel_flags.contains(required_flags) {
+            warn!("Indirect drawing isn't supported on this device, using per mesh draw calls");
+            return;
+        }
+
+        match IndirectDrawer::new(&wgpu_structs.device) {
+            Ok(indirect_drawer) => {
+                info!("Indirect drawing enabled");
+                self.indirect_drawer = Some(indirect_drawer);
+            },
+            Err(e) => warn!("Couldn't create indirect drawer: {}", e)
         }
     }
+    pub fn get_indirect_drawer(&self) -> Option<&IndirectDrawer> {
+        self.indirect_drawer.as_ref()
+    }
 
-    pub fn get_mesh(&self, mesh_index: usize) -> Option<&Box<dyn MeshRenderer>> {
-        self.meshes.get(mesh_index)
+    pub fn get_mesh(&self, mesh_index: usize) -> Option<&dyn MeshRenderer> {
+        self.meshes.get(mesh_index).map(|mesh| mesh.as_ref())
     }
     pub fn create_mesh_instance(&mut self, mesh_index: usize) -> Option<usize> {
         match self.meshes.get_mut(mesh_index) {
@@ -27,9 +54,93 @@ impl MeshManager {
             }
         }
     }
+    pub fn remove_mesh_instance(&mut self, mesh_index: usize, mesh_instance_index: usize) {
+        match self.meshes.get_mut(mesh_index) {
+            Some(mesh) => {
+                if let Err(e) = mesh.remove_instance(mesh_instance_index) {
+                    warn!("Couldn't remove instance {} of mesh {}: {}", mesh_instance_index, mesh_index, e);
+                }
+            },
+            None => warn!("Couldn't find mesh at index {}", mesh_index)
+        }
+    }
     pub fn add_mesh(&mut self, mesh: impl MeshRenderer + 'static) {
         self.meshes.push(Box::new(mesh));
     }
+    // The indirect drawer has its own buffers
+    pub fn reserve_instance_buffers(&mut self, device: &wgpu::Device) {
+        if self.indirect_drawer.is_none() {
+            self.meshes.iter_mut().for_each(|mesh| mesh.reserve_instance_buffer(device));
+        }
+    }
+
+    pub fn cull_instances(&mut self, frustum: &Frustum) -> CullingStats {
+        if let Some(indirect_drawer) = self.indirect_drawer.as_mut() {
+            return indirect_drawer.prepare(&self.meshes, frustum);
+        }
+
+        let mut culling_stats = CullingStats::default();
+        for mesh in self.meshes.iter_mut() {
+            culling_stats += mesh.cull_instances(frustum);
+        }
+        culling_stats
+    }
+    fn get_lod_distance(&self, lod_group: &LodGroup, threshold: &LodThreshold, camera_fovy: cgmath::Deg<f32>) -> f32 {
+        match threshold {
+            LodThreshold::Distance(distance) => *distance,
+            LodThreshold::ScreenSize(screen_size) => {
+                // Size is relative to the most detailed level, so every level switches at the same spot
+                let radius = lod_group.levels.first()
+                    .and_then(|level| self.meshes.get(level.mesh_index))
+                    .map_or(0.0, |mesh| mesh.get_bounding_sphere().radius);
+                let half_fovy_tan = cgmath::Angle::tan(camera_fovy / 2.0);
+
+                radius / (screen_size * half_fovy_tan)
+            }
+        }
+    }
+
+    // Picks the level for every group from its distance to the camera. The other levels are
+    // hidden, so culling skips them, and a level inside the cross-fade band dithers into the next one
+    pub fn update_lod_groups(&mut self, lod_groups: Vec<(LodGroup, Transform)>, camera_position: cgmath::Point3<f32>, camera_fovy: cgmath::Deg<f32>) {
+        for (lod_group, transform) in lod_groups.iter() {
+            let distance = (transform.position - camera_position.to_vec()).magnitude();
+            let lod_distances: Vec<f32> = lod_group.levels.iter()
+                .map(|level| self.get_lod_distance(lod_group, &level.threshold, camera_fovy))
+                .collect();
+
+            let mut fades = vec![0.0; lod_group.levels.len()];
+            if let Some(selected) = lod_distances.iter().position(|lod_distance| distance <= *lod_distance) {
+                fades[selected] = 1.0;
+
+                if let Some(cross_fade) = lod_group.cross_fade.filter(|cross_fade| *cross_fade > 0.0) {
+                    let fade_start = lod_distances[selected] - cross_fade;
+                    if distance > fade_start {
+                        let fade = 1.0 - (distance - fade_start) / cross_fade;
+                        fades[selected] = fade;
+                        if let Some(next_fade) = fades.get_mut(selected + 1) {
+                            *next_fade = -fade;
+                        }
+                    }
+                }
+            }
+
+            for (level, fade) in lod_group.levels.iter().zip(fades) {
+                let mut transform_instance = TransformInstance::from(transform);
+                transform_instance.fade = fade;
+
+                let result = self.meshes.get_mut(level.mesh_index)
+                    .map(|mesh| mesh.update_instance_data(level.mesh_instance_index, transform_instance));
+
+                match result {
+                    Some(Err(e)) => warn!("Error updating LOD instance: {}", e),
+                    None => warn!("Couldn't find LOD mesh at index {}", level.mesh_index),
+                    _ => ()
+                }
+            }
+        }
+    }
+
     pub fn get_meshes(&self) -> &Vec<Box<dyn MeshRenderer>> {
          &self.meshes
     }
@@ -37,3 +148,9 @@ impl MeshManager {
          &mut self.meshes
     }
 }
+
+impl Default for MeshManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,23 @@
 mod main_renderer;
 mod editor_renderer;
-mod renderer;
+mod renderer_loop;
 mod mesh;
 mod mesh_manager;
 mod transform_instance;
+mod render_targets;
+mod render_graph;
+mod culling;
+mod indirect;
+mod extract;
 
 pub use editor_renderer::EditorRenderer;
-pub use renderer::{Renderer, RendererLoop};
+pub use renderer_loop::{Renderer, RendererLoop};
 pub use main_renderer::MainRenderer;
 pub use mesh::TexturedMesh;
 pub use transform_instance::TransformInstance;
 pub use mesh_manager::MeshManager;
+pub use render_targets::RenderTargets;
+pub use render_graph::{RenderGraph, TexturePool};
+pub use culling::{Aabb, BoundingSphere, Frustum, CullingStats};
+pub use indirect::IndirectDrawer;
+pub use extract::{ExtractedCamera, ExtractedMeshInstances, ExtractedLodGroups, insert_extract_resources, move_extracted};
@@ -0,0 +1,379 @@
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use log::info;
+
+use crate::topological_sort::topological_sort;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ResourceHandle(usize);
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TextureDesc {
+    pub label: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    pub usage: wgpu::TextureUsages
+}
+
+// Every write makes a new version of a resource, a pass reads the latest version when it's added.
+// So a pass never sees what a pass added after it writes, even if they share the texture
+#[derive(Clone, Default)]
+struct ResourceVersion {
+    writer: Option<usize>,
+    readers: Vec<usize>
+}
+
+enum GraphResource<'a> {
+    Transient(TextureDesc),
+    Imported(&'a wgpu::TextureView)
+}
+
+pub struct PassResources<'r> {
+    views: Vec<Option<&'r wgpu::TextureView>>
+}
+
+impl<'r> PassResources<'r> {
+    pub fn view(&self, handle: ResourceHandle) -> &'r wgpu::TextureView {
+        self.views[handle.0].expect("Pass used a resource it didn't declare")
+    }
+}
+
+type PassExecuteFn<'a> = Box<dyn for<'r> FnOnce(&mut wgpu::CommandEncoder, &PassResources<'r>) + 'a>;
+
+struct RenderGraphPass<'a> {
+    label: &'static str,
+    reads: Vec<ResourceHandle>,
+    writes: Vec<ResourceHandle>,
+    execute: PassExecuteFn<'a>
+}
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+    CyclicDependency(Vec<&'static str>)
+}
+
+impl Display for RenderGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderGraphError::CyclicDependency(passes) => write!(f, "Render graph has a cycle between passes: {:?}", passes)
+        }
+    }
+}
+
+impl Error for RenderGraphError {}
+
+// Built every frame, the textures it allocates are kept around in the TexturePool
+pub struct RenderGraph<'a> {
+    resources: Vec<GraphResource<'a>>,
+    // Versions of every resource, the first one is its content before any pass
+    versions: Vec<Vec<ResourceVersion>>,
+    passes: Vec<RenderGraphPass<'a>>
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            resources: vec![],
+            versions: vec![],
+            passes: vec![]
+        }
+    }
+
+    pub fn create_texture(&mut self, desc: TextureDesc) -> ResourceHandle {
+        self.resources.push(GraphResource::Transient(desc));
+        self.versions.push(vec![ResourceVersion::default()]);
+        ResourceHandle(self.resources.len() - 1)
+    }
+
+    pub fn import_texture(&mut self, view: &'a wgpu::TextureView) -> ResourceHandle {
+        self.resources.push(GraphResource::Imported(view));
+        self.versions.push(vec![ResourceVersion::default()]);
+        ResourceHandle(self.resources.len() - 1)
+    }
+
+    pub fn add_pass<F>(&mut self, label: &'static str, reads: &[ResourceHandle], writes: &[ResourceHandle], execute: F)
+        where F: for<'r> FnOnce(&mut wgpu::CommandEncoder, &PassResources<'r>) + 'a
+    {
+        let pass_index = self.passes.len();
+        for read in reads.iter() {
+            if let Some(version) = self.versions[read.0].last_mut() {
+                version.readers.push(pass_index);
+            }
+        }
+        for write in writes.iter() {
+            self.versions[write.0].push(ResourceVersion {
+                writer: Some(pass_index),
+                readers: vec![]
+            });
+        }
+
+        self.passes.push(RenderGraphPass {
+            label,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            execute: Box::new(execute)
+        });
+    }
+
+    // A pass reading a version runs after the pass that wrote it. The pass writing the next version
+    // runs after the previous writer and after everything that read the previous version
+    fn get_dependencies(&self) -> Vec<Vec<usize>> {
+        let mut dependencies: Vec<Vec<usize>> = vec![vec![]; self.passes.len()];
+
+        for versions in self.versions.iter() {
+            for (index, version) in versions.iter().enumerate() {
+                if let Some(writer) = version.writer {
+                    for reader in version.readers.iter().filter(|reader| **reader != writer) {
+                        dependencies[*reader].push(writer);
+                    }
+                }
+
+                if let Some(next_writer) = versions.get(index + 1).and_then(|next| next.writer) {
+                    let previous = version.writer.iter().chain(version.readers.iter());
+                    for pass_index in previous.filter(|pass_index| **pass_index != next_writer) {
+                        dependencies[next_writer].push(*pass_index);
+                    }
+                }
+            }
+        }
+
+        for deps in dependencies.iter_mut() {
+            deps.sort();
+            deps.dedup();
+        }
+        dependencies
+    }
+
+    fn sort_passes(&self) -> Result<Vec<usize>, RenderGraphError> {
+        topological_sort(&self.get_dependencies())
+            .map_err(|unsorted| RenderGraphError::CyclicDependency(unsorted.iter().map(|index| self.passes[*index].label).collect()))
+    }
+
+    // First and last position in the pass order where a resource is used
+    fn get_lifetimes(&self, order: &[usize]) -> Vec<Option<(usize, usize)>> {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+
+        for (position, pass_index) in order.iter().enumerate() {
+            let pass = &self.passes[*pass_index];
+            for handle in pass.reads.iter().chain(pass.writes.iter()) {
+                lifetimes[handle.0] = match lifetimes[handle.0] {
+                    Some((first, _)) => Some((first, position)),
+                    None => Some((position, position))
+                };
+            }
+        }
+
+        lifetimes
+    }
+
+    pub fn execute(self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture_pool: &mut TexturePool)
+        -> Result<(), RenderGraphError> {
+        let order = self.sort_passes()?;
+        let lifetimes = self.get_lifetimes(&order);
+
+        let mut transient: Vec<(usize, &TextureDesc, (usize, usize))> = self.resources.iter()
+            .enumerate()
+            .filter_map(|(index, resource)| match (resource, lifetimes[index]) {
+                (GraphResource::Transient(desc), Some(lifetime)) => Some((index, desc, lifetime)),
+                _ => None
+            })
+            .collect();
+        transient.sort_by_key(|(_, _, (first, _))| *first);
+
+        let slots = texture_pool.allocate(device, &transient);
+
+        {
+            let views = self.resources.iter()
+                .enumerate()
+                .map(|(index, resource)| match resource {
+                    GraphResource::Imported(view) => Some(*view),
+                    GraphResource::Transient(_) => slots.get(&index).map(|slot| texture_pool.get_view(*slot))
+                })
+                .collect();
+            let pass_resources = PassResources { views };
+
+            let mut passes: Vec<Option<RenderGraphPass>> = self.passes.into_iter().map(Some).collect();
+            for pass_index in order {
+                if let Some(pass) = passes[pass_index].take() {
+                    (pass.execute)(encoder, &pass_resources);
+                }
+            }
+        }
+
+        texture_pool.release_unused();
+        Ok(())
+    }
+}
+
+impl Default for RenderGraph<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct PooledTexture {
+    desc: TextureDesc,
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    is_used: bool
+}
+
+// Owned by the renderer so graph textures survive between frames.
+// Textures that aren't used in a frame (old sizes after a resize) get dropped
+pub struct TexturePool {
+    textures: Vec<PooledTexture>
+}
+
+impl TexturePool {
+    pub fn new() -> Self {
+        Self {
+            textures: vec![]
+        }
+    }
+
+    // Resources whose lifetimes don't overlap and have the same description share a texture
+    fn allocate(&mut self, device: &wgpu::Device, transient: &Vec<(usize, &TextureDesc, (usize, usize))>)
+        -> HashMap<usize, usize> {
+        let mut busy_until: Vec<Option<usize>> = vec![None; self.textures.len()];
+        let mut slots = HashMap::new();
+
+        self.textures.iter_mut().for_each(|texture| texture.is_used = false);
+
+        for (resource_index, desc, (first, last)) in transient.iter() {
+            let free_slot = (0..self.textures.len())
+                .find(|slot| self.textures[*slot].desc == **desc && busy_until[*slot].is_none_or(|until| until < *first));
+
+            let slot = match free_slot {
+                Some(slot) => slot,
+                None => {
+                    self.textures.push(Self::create_texture(device, desc));
+                    busy_until.push(None);
+                    self.textures.len() - 1
+                }
+            };
+
+            busy_until[slot] = Some(*last);
+            self.textures[slot].is_used = true;
+            slots.insert(*resource_index, slot);
+        }
+
+        slots
+    }
+
+    fn create_texture(device: &wgpu::Device, desc: &TextureDesc) -> PooledTexture {
+        info!("Allocating render graph texture: {} ({}x{}, {} samples)", desc.label, desc.width, desc.height, desc.sample_count);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(desc.label),
+            size: wgpu::Extent3d {
+                width: desc.width,
+                height: desc.height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: desc.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[]
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        PooledTexture {
+            desc: desc.clone(),
+            _texture: texture,
+            view,
+            is_used: true
+        }
+    }
+
+    fn get_view(&self, slot: usize) -> &wgpu::TextureView {
+        &self.textures[slot].view
+    }
+
+    fn release_unused(&mut self) {
+        self.textures.retain(|texture| texture.is_used);
+    }
+}
+
+impl Default for TexturePool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(graph: &mut RenderGraph, label: &'static str) -> ResourceHandle {
+        graph.create_texture(TextureDesc {
+            label,
+            width: 16,
+            height: 16,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+        })
+    }
+
+    fn labels(graph: &RenderGraph, order: &[usize]) -> Vec<&'static str> {
+        order.iter().map(|pass_index| graph.passes[*pass_index].label).collect()
+    }
+
+    #[test]
+    fn reader_sees_the_version_before_the_next_write() {
+        let mut graph = RenderGraph::new();
+        let color = texture(&mut graph, "color");
+        graph.add_pass("write", &[], &[color], |_, _| {});
+        graph.add_pass("read", &[color], &[], |_, _| {});
+        graph.add_pass("overwrite", &[], &[color], |_, _| {});
+
+        let order = graph.sort_passes().unwrap();
+        assert_eq!(labels(&graph, &order), vec!["write", "read", "overwrite"]);
+        assert_eq!(graph.get_dependencies(), vec![vec![], vec![0], vec![0, 1]]);
+    }
+
+    #[test]
+    fn read_modify_write_chains_passes() {
+        let mut graph = RenderGraph::new();
+        let color = texture(&mut graph, "color");
+        graph.add_pass("clear", &[], &[color], |_, _| {});
+        graph.add_pass("blend", &[color], &[color], |_, _| {});
+        graph.add_pass("present", &[color], &[], |_, _| {});
+
+        assert_eq!(graph.get_dependencies(), vec![vec![], vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn independent_passes_keep_their_order() {
+        let mut graph = RenderGraph::new();
+        let shadow = texture(&mut graph, "shadow");
+        let color = texture(&mut graph, "color");
+        graph.add_pass("color", &[], &[color], |_, _| {});
+        graph.add_pass("shadow", &[], &[shadow], |_, _| {});
+        graph.add_pass("lit", &[shadow, color], &[color], |_, _| {});
+
+        let order = graph.sort_passes().unwrap();
+        assert_eq!(labels(&graph, &order), vec!["color", "shadow", "lit"]);
+    }
+
+    #[test]
+    fn lifetimes_cover_first_to_last_use() {
+        let mut graph = RenderGraph::new();
+        let first = texture(&mut graph, "first");
+        let second = texture(&mut graph, "second");
+        let unused = texture(&mut graph, "unused");
+        graph.add_pass("a", &[], &[first], |_, _| {});
+        graph.add_pass("b", &[first], &[second], |_, _| {});
+        graph.add_pass("c", &[second], &[], |_, _| {});
+
+        let order = graph.sort_passes().unwrap();
+        let lifetimes = graph.get_lifetimes(&order);
+        assert_eq!(lifetimes[first.0], Some((0, 1)));
+        assert_eq!(lifetimes[second.0], Some((1, 2)));
+        assert_eq!(lifetimes[unused.0], None);
+    }
+}
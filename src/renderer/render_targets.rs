@@ -0,0 +1,80 @@
+use crate::{texture::Texture, WgpuStructs};
+
+use super::render_graph::{RenderGraph, ResourceHandle, TextureDesc, PassResources};
+
+// Surface, multisampled color and depth declared on a render graph for a frame
+#[derive(Clone, Copy)]
+pub struct RenderTargets {
+    pub surface: ResourceHandle,
+    pub msaa_color: Option<ResourceHandle>,
+    pub depth: ResourceHandle
+}
+
+impl RenderTargets {
+    pub fn declare<'a>(graph: &mut RenderGraph<'a>, surface_view: &'a wgpu::TextureView, wgpu_structs: &WgpuStructs) -> Self {
+        let WgpuStructs { config, sample_count, .. } = wgpu_structs;
+
+        let surface = graph.import_texture(surface_view);
+
+        // With a single sample we render straight into the surface, nothing to resolve
+        let msaa_color = if *sample_count > 1 {
+            Some(graph.create_texture(TextureDesc {
+                label: "Multisampled color texture",
+                width: config.width,
+                height: config.height,
+                format: config.format,
+                sample_count: *sample_count,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            }))
+        } else { None };
+
+        let depth = graph.create_texture(TextureDesc {
+            label: "Depth texture",
+            width: config.width,
+            height: config.height,
+            format: Texture::DEPTH_FORMAT,
+            sample_count: *sample_count,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        });
+
+        Self {
+            surface,
+            msaa_color,
+            depth
+        }
+    }
+
+    pub fn get_writes(&self) -> Vec<ResourceHandle> {
+        let mut writes = vec![self.surface, self.depth];
+        writes.extend(self.msaa_color);
+        writes
+    }
+
+    pub fn color_attachment<'r>(&self, resources: &PassResources<'r>, clear_color: wgpu::Color) -> wgpu::RenderPassColorAttachment<'r> {
+        let surface_view = resources.view(self.surface);
+        let (view, resolve_target) = match self.msaa_color {
+            Some(msaa_color) => (resources.view(msaa_color), Some(surface_view)),
+            None => (surface_view, None)
+        };
+
+        wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear_color),
+                store: true
+            }
+        }
+    }
+
+    pub fn depth_stencil_attachment<'r>(&self, resources: &PassResources<'r>) -> wgpu::RenderPassDepthStencilAttachment<'r> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: resources.view(self.depth),
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: true
+            }),
+            stencil_ops: None
+        }
+    }
+}
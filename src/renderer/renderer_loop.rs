@@ -0,0 +1,40 @@
+use wgpu::RenderPass;
+use winit::{window::Window};
+
+use crate::{WgpuStructs, RendererResources};
+use crate::entities::components::{MeshRenderer, MeshInstance, LodGroup, Transform};
+
+use super::MeshManager;
+
+pub trait Renderer {
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, scale_factor: Option<f32>);
+    fn render<'a>(&'a mut self, wgpu_structs: &WgpuStructs, window: &Window, renderer_resources: &'a mut RendererResources) -> Result<(), wgpu::SurfaceError>;
+    fn add_mesh(&mut self, mesh: impl MeshRenderer + 'static);
+    fn update_meshes(&mut self, mesh_instances: Vec<MeshInstance>, renderer_resources: &mut RendererResources);
+    fn update_lod_groups(&mut self, lod_groups: Vec<(LodGroup, Transform)>, renderer_resources: &RendererResources);
+    fn get_mesh_manager(&self) -> &MeshManager;
+    fn get_mesh_manager_mut(&mut self) -> &mut MeshManager;
+}
+
+pub struct RendererLoop;
+
+impl RendererLoop {
+    pub fn update(queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, renderer_resources: &RendererResources, mesh_manager: &MeshManager) {
+        let RendererResources { camera_uniform, .. } = renderer_resources;
+        let meshes = mesh_manager.get_meshes();
+        meshes.iter().for_each(|mesh| mesh.update_camera(queue, &[*camera_uniform]));
+
+        match mesh_manager.get_indirect_drawer() {
+            Some(indirect_drawer) => indirect_drawer.update(queue, encoder),
+            None => meshes.iter().for_each(|mesh| mesh.write_instance_data(queue))
+        }
+    }
+
+    pub fn render<'a>(render_pass: &mut RenderPass<'a>, _renderer_resources: &'a RendererResources, mesh_manager: &'a MeshManager) {
+        let meshes = mesh_manager.get_meshes();
+        match mesh_manager.get_indirect_drawer() {
+            Some(indirect_drawer) => indirect_drawer.render(render_pass, meshes),
+            None => meshes.iter().for_each(|mesh| mesh.render(render_pass))
+        }
+    }
+}
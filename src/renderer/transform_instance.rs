@@ -1,23 +1,28 @@
 use cgmath::{Vector3, Quaternion};
 use wgpu::VertexAttribute;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct TransformInstance {
     pub position: Vector3<f32>,
-    pub rotation: Quaternion<f32>
+    pub rotation: Quaternion<f32>,
+    // 1.0 is fully visible and 0.0 hidden. In between the instance is dithered,
+    // negative values use the inverse pattern so two LOD levels can cross-fade
+    pub fade: f32
 }
 
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct TransformInstanceRaw {
-    model: [[f32; 4]; 4]
+    model: [[f32; 4]; 4],
+    fade: f32
 }
 
 impl From<&TransformInstance> for TransformInstanceRaw {
     fn from(value: &TransformInstance) -> Self {
         Self { 
-            model: (cgmath::Matrix4::from(value.rotation) * cgmath::Matrix4::from_translation(value.position)).into() 
+            model: value.to_matrix().into(),
+            fade: value.fade
         }
     }
 }
@@ -26,12 +31,17 @@ impl Default for TransformInstance {
     fn default() -> Self {
         Self {
             position: Vector3::new(0.0, 0.0, 0.0),
-            rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0)
+            rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            fade: 1.0
         }
     }
 }
 
 impl TransformInstance {
+    pub fn to_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from(self.rotation) * cgmath::Matrix4::from_translation(self.position)
+    }
+
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout { 
             array_stride: std::mem::size_of::<TransformInstanceRaw>() as wgpu::BufferAddress, 
@@ -56,6 +66,11 @@ impl TransformInstance {
                     offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
                     format: wgpu::VertexFormat::Float32x4,
                     shader_location: 8
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    format: wgpu::VertexFormat::Float32,
+                    shader_location: 9
                 }
             ]
         }
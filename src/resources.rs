@@ -0,0 +1,43 @@
+use std::{any::{Any, TypeId}, collections::HashMap};
+
+use probable_spork_ecs::{AtomicRef, AtomicRefCell, AtomicRefMut};
+
+// Singletons shared by systems and the editor, at most one value per type.
+// Systems run on several threads, so resources have to be Send + Sync
+pub struct Resources {
+    resources: HashMap<TypeId, AtomicRefCell<Box<dyn Any + Send + Sync>>>
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self {
+            resources: HashMap::new()
+        }
+    }
+
+    // Replaces the previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, resource: T) {
+        self.resources.insert(TypeId::of::<T>(), AtomicRefCell::new(Box::new(resource)));
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.resources.remove(&TypeId::of::<T>())
+            .map(|resource| *resource.into_inner().downcast::<T>().expect("Resource stored under the wrong type"))
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<AtomicRef<'_, T>> {
+        self.resources.get(&TypeId::of::<T>())
+            .map(|resource| AtomicRef::map(resource.borrow(), |resource| resource.downcast_ref::<T>().expect("Resource stored under the wrong type")))
+    }
+
+    pub fn get_mut<T: 'static>(&self) -> Option<AtomicRefMut<'_, T>> {
+        self.resources.get(&TypeId::of::<T>())
+            .map(|resource| AtomicRefMut::map(resource.borrow_mut(), |resource| resource.downcast_mut::<T>().expect("Resource stored under the wrong type")))
+    }
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
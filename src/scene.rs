@@ -1,12 +1,31 @@
-use std::{cell::{RefCell, Ref}, borrow::Borrow};
+use std::{any::{Any, TypeId}, sync::{Mutex, MutexGuard, atomic::{AtomicU32, Ordering}}};
 
-use log::{info, warn};
-use probable_spork_ecs::{component::{ComponentStorage, Entity, Component, self}};
+use log::warn;
+use probable_spork_ecs::{component::{ComponentStorage, Entity, Component}, change_detection::Mut, AtomicRef, AtomicRefMut};
+use rayon::prelude::*;
 
-use crate::{script::Script, entities::components::MeshInstance};
+use crate::{script::{self, Script, ScriptContext}, script_registry, time::Time, entities::components::{MeshInstance, LodGroup, Transform, Name, Tags, PrefabInstance, ScriptEnabled, Parent}, query::{Query, QueryParam, QueryFilter, QueryError}, resources::Resources, events::{Events, EventWriter, EventChannel}, prefab::{Prefab, PrefabHandle}, renderer::MeshManager, commands::Commands, scripting::ScriptedBehaviour};
+
+// State shared by every node of a prefab being spawned
+struct PrefabSpawn<'a> {
+    handle: PrefabHandle,
+    mesh_manager: &'a mut MeshManager,
+    // Scripts of these get set up once the whole subtree exists
+    spawned: Vec<Entity>
+}
 
 pub struct Scene {
     pub component_storage: ComponentStorage,
+    resources: Resources,
+    // Change tick at the start of the frame, queries outside of systems see changes made since then
+    frame_tick: AtomicU32,
+    event_channels: Vec<EventChannel>,
+    prefabs: Vec<Prefab>,
+    // Scripts added after setup_components have to be set up on their own
+    is_set_up: bool,
+    commands: Mutex<Commands>,
+    // Renderer instances of despawned entities and removed MeshInstances and LodGroups, freed by the renderer after the next extract
+    removed_mesh_instances: Mutex<Vec<MeshInstance>>
 }
 
 
@@ -14,59 +33,733 @@ impl Scene {
     pub fn new() -> Self {
         Self {
             component_storage: ComponentStorage::new(),
+            resources: Resources::new(),
+            frame_tick: AtomicU32::new(1),
+            event_channels: vec![],
+            prefabs: vec![],
+            is_set_up: false,
+            commands: Mutex::new(Commands::new()),
+            removed_mesh_instances: Mutex::new(vec![])
+        }
+    }
+
+    pub fn get_commands_mut(&self) -> MutexGuard<'_, Commands> {
+        self.commands.lock().expect("Commands lock poisoned")
+    }
+
+    // Called once the frame's systems have run
+    pub fn apply_commands(&mut self) {
+        let commands = self.commands.get_mut().expect("Commands lock poisoned");
+        if commands.is_empty() {
+            return;
+        }
+        let commands = std::mem::replace(commands, Commands::new());
+        commands.apply(self);
+    }
+
+    // Children go first. The script's on_destroy runs before Component::on_destroy for every component
+    pub fn despawn(&mut self, entity: &Entity) {
+        if !self.is_alive(entity) {
+            warn!("Entity {} is already despawned", entity);
+            return;
+        }
+
+        for child in self.get_children(entity) {
+            self.despawn(&child);
+        }
+
+        self.destroy_script(entity);
+        if let Some(mesh_instance) = self.component_storage.get_entity_component::<MeshInstance>(entity) {
+            self.free_render_instances(&*mesh_instance);
+        }
+        if let Some(lod_group) = self.component_storage.get_entity_component::<LodGroup>(entity) {
+            self.free_render_instances(&*lod_group);
         }
+        self.component_storage.despawn(entity);
     }
 
-    pub fn setup_components(&self) {
+    // Same hooks as despawn, only for the one component
+    pub fn remove_component<T: Component + 'static>(&mut self, entity: &Entity) -> Option<T> {
+        if TypeId::of::<T>() == TypeId::of::<Box<dyn Script>>() {
+            self.destroy_script(entity);
+        }
+
+        let component = self.component_storage.remove_component::<T>(entity)?;
+        self.free_render_instances(&component);
+        Some(component)
+    }
+
+    // A MeshInstance owns one renderer instance, a LodGroup one per level. Other components don't own any
+    fn free_render_instances(&self, component: &dyn Any) {
+        let instances = match (component.downcast_ref::<MeshInstance>(), component.downcast_ref::<LodGroup>()) {
+            (Some(mesh_instance), _) => vec![mesh_instance.clone()],
+            (_, Some(lod_group)) => lod_group.levels.iter()
+                .map(|level| MeshInstance {
+                    mesh_index: level.mesh_index,
+                    mesh_instance_index: level.mesh_instance_index,
+                    local_transform: Transform::default()
+                })
+                .collect(),
+            _ => return
+        };
+        self.removed_mesh_instances.lock().expect("Removed mesh instances lock poisoned").extend(instances);
+    }
+
+    pub fn is_alive(&self, entity: &Entity) -> bool {
+        self.component_storage.is_alive(entity)
+    }
+
+    pub fn take_removed_mesh_instances(&self) -> Vec<MeshInstance> {
+        std::mem::take(&mut *self.removed_mesh_instances.lock().expect("Removed mesh instances lock poisoned"))
+    }
+
+    pub fn add_event<T: std::fmt::Debug + Send + Sync + 'static>(&mut self) {
+        if self.event_channels.iter().any(|channel| channel.type_name == std::any::type_name::<T>()) {
+            return;
+        }
+        self.insert_resource(Events::<T>::new());
+        self.event_channels.push(EventChannel::of::<T>());
+    }
+
+    pub fn event_writer<T: 'static>(&self) -> Option<EventWriter<'_, T>> {
+        self.resource_mut::<Events<T>>().map(EventWriter::new)
+    }
+
+    pub fn send_event<T: 'static>(&self, event: T) {
+        match self.event_writer::<T>() {
+            Some(mut event_writer) => event_writer.send(event),
+            None => warn!("Event {} isn't registered", std::any::type_name::<T>())
+        }
+    }
+
+    // Called once at the start of every frame
+    pub fn update_events(&self) {
+        self.event_channels.iter().for_each(|channel| channel.update(self));
+    }
+
+    pub fn get_event_channels(&self) -> &Vec<EventChannel> {
+        &self.event_channels
+    }
+
+    // Called once at the start of every frame
+    pub fn advance_change_tick(&self) {
+        self.frame_tick.store(self.component_storage.increment_change_tick(), Ordering::Relaxed);
+    }
+
+    // Last run tick for queries that don't run in a system
+    pub fn get_frame_tick(&self) -> u32 {
+        self.frame_tick.load(Ordering::Relaxed) - 1
+    }
+
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, resource: T) {
+        self.resources.insert(resource);
+    }
+
+    // Lets a resource borrow the rest of the scene mutably, insert it back afterwards
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        self.resources.remove::<T>()
+    }
+
+    pub fn resource<T: 'static>(&self) -> Option<AtomicRef<'_, T>> {
+        self.resources.get::<T>()
+    }
+
+    pub fn resource_mut<T: 'static>(&self) -> Option<AtomicRefMut<'_, T>> {
+        self.resources.get_mut::<T>()
+    }
+
+    pub fn setup_components(&mut self) {
+        self.is_set_up = true;
         self.component_storage.setup_components();
+        self.with_script_context(|context| self.for_each_script(|script| {
+            script.run_hook(&self.component_storage, |script| script.script_setup(context));
+        }));
     }
 
     pub fn update_components(&self) {
         self.component_storage.update_components();
+        self.run_script_hook(|script, context| script.script_update(context));
     }
 
     pub fn create_entity(&mut self) -> Entity {
-        self.component_storage.create_entity()
+        let entity = self.component_storage.create_entity();
+        self.add_component_to_entity(&entity, Name(format!("Entity {}", entity.index())));
+        entity
+    }
+
+    // Marks the component as changed when it's written through
+    pub fn get_component_mut<T: Component + 'static>(&self, entity: &Entity) -> Option<Mut<'_, T>> {
+        self.component_storage.get_entity_component_mut::<T>(entity)
     }
 
+    pub fn rename_entity(&self, entity: &Entity, name: String) {
+        match self.component_storage.get_entity_component_mut::<Name>(entity) {
+            Some(mut entity_name) => entity_name.0 = name,
+            None => warn!("Couldn't find Name for entity: {}", entity)
+        }
+    }
+
+    pub fn add_tag(&mut self, entity: &Entity, tag: &str) {
+        let has_tags = match self.component_storage.get_entity_component_mut::<Tags>(entity) {
+            Some(mut tags) => {
+                if !tags.contains(tag) {
+                    tags.0.push(tag.to_string());
+                }
+                true
+            },
+            None => false
+        };
+
+        if !has_tags {
+            self.add_component_to_entity(entity, Tags(vec![tag.to_string()]));
+        }
+    }
+
+    pub fn get_entity_names(&self) -> Vec<(Entity, String)> {
+        match self.query::<(Entity, Option<&Name>)>() {
+            Ok(query) => {
+                return query
+                    .map(|(entity, name)| {
+                        let name = name.map_or_else(|| format!("Entity {}", entity.index()), |name| name.0.clone());
+                        (entity, name)
+                    })
+                    .collect();
+            },
+            Err(e) => warn!("Couldn't query entity names: {}", e)
+        }
+        vec![]
+    }
+
+    // First entity with the name, names aren't unique
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.query::<(Entity, &Name)>().ok()?
+            .find(|(_, entity_name)| entity_name.0 == name)
+            .map(|(entity, _)| entity)
+    }
+
+    pub fn find_by_tag(&self, tag: &str) -> Vec<Entity> {
+        match self.query::<(Entity, &Tags)>() {
+            Ok(query) => query
+                .filter(|(_, tags)| tags.contains(tag))
+                .map(|(entity, _)| entity)
+                .collect(),
+            Err(e) => {
+                warn!("Couldn't query tags: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    // A Box<dyn Script> gets the same setup as add_boxed_script_to_entity,
+    // a ScriptedBehaviour gets a ScriptEnabled so it can be toggled like a script
     pub fn add_component_to_entity<T: Component + 'static>(&mut self, entity: &Entity, component: T) {
-        self.component_storage.register_component(&entity, component);
+        match script::into_script(component) {
+            Ok(script) => self.add_boxed_script_to_entity(entity, script),
+            Err(component) => {
+                if (&component as &dyn Any).is::<ScriptedBehaviour>() {
+                    self.component_storage.register_component(entity, ScriptEnabled::default());
+                }
+                self.component_storage.register_component(entity, component)
+            }
+        }
     }
 
     pub fn update_entity_component<T>(&mut self, entity: &Entity, component: T)
         where T: Component + Clone + 'static
     {
         match self.component_storage.get_entity_component_mut::<T>(entity) {
-            Some(mut c) => {
-                *c = component.clone();
+            Some(mut c) => *c = component,
+            None => warn!("Couldn't find {} for entity: {}", std::any::type_name::<T>(), entity)
+        }
+    }
+
+    pub fn query<'w, Q: QueryParam<'w>>(&'w self) -> Result<Query<'w, Q>, QueryError> {
+        Query::new(self, self.get_frame_tick())
+    }
+
+    // Added and Changed filters match changes made since the start of the frame
+    pub fn query_filtered<'w, Q: QueryParam<'w>, F: QueryFilter>(&'w self) -> Result<Query<'w, Q, F>, QueryError> {
+        Query::new(self, self.get_frame_tick())
+    }
+
+    // For systems, Added and Changed filters match changes made since the system's last run
+    pub fn query_filtered_since<'w, Q: QueryParam<'w>, F: QueryFilter>(&'w self, last_run: u32) -> Result<Query<'w, Q, F>, QueryError> {
+        Query::new(self, last_run)
+    }
+
+    // Only instances whose mesh, entity transform or one of the parent transforms changed since last_run
+    pub fn get_changed_mesh_instances(&self, last_run: u32) -> Vec<MeshInstance> {
+        let is_changed = |entity: &Entity| {
+            let mesh_ticks = self.component_storage.get_component_ticks::<MeshInstance>(entity);
+            let parent_ticks = self.component_storage.get_component_ticks::<Parent>(entity);
+            let is_transform_changed = |entity: &Entity| self.component_storage.get_component_ticks::<Transform>(entity)
+                .is_some_and(|ticks| ticks.is_changed(last_run));
+
+            mesh_ticks.is_some_and(|ticks| ticks.is_changed(last_run)) || parent_ticks.is_some_and(|ticks| ticks.is_changed(last_run))
+                || is_transform_changed(entity) || self.get_ancestors(entity).iter().any(is_transform_changed)
+        };
+
+        match self.query::<(Entity, &MeshInstance)>() {
+            Ok(query) => {
+                return query
+                    .filter(|(entity, _)| is_changed(entity))
+                    .map(|(entity, mesh_instance)| mesh_instance.with_parent_transform(&self.get_world_transform(&entity)))
+                    .collect();
             },
-            None => warn!("Couldn't find {} for entity: {}", std::any::type_name::<T>(), entity.0)
+            Err(e) => warn!("Couldn't query mesh instances: {}", e)
         }
+        vec![]
     }
 
-    pub fn get_mesh_instances(&self) -> Vec<MeshInstance> {
-        let mesh_instances_opt = self.component_storage.get_component_vec::<MeshInstance>();
-        match mesh_instances_opt {
-            Some(mesh_instances) => {
-                return mesh_instances
-                    .iter()
-                    .enumerate()
-                    .map(|(entity_id, instance)| {
-                        let new_instance = instance.borrow().clone();
-                        MeshInstance::apply_transforms_to_mesh_instance(self, entity_id, new_instance)
-                    })
+    pub fn get_lod_groups(&self) -> Vec<(LodGroup, Transform)> {
+        match self.query::<(Entity, &LodGroup)>() {
+            Ok(query) => {
+                return query
+                    .map(|(entity, lod_group)| (lod_group.clone(), self.get_world_transform(&entity)))
                     .collect();
             },
-            None => warn!("Couldn't find any mesh instances")
+            Err(e) => warn!("Couldn't query LOD groups: {}", e)
         }
         vec![]
     }
 
+    pub fn get_children(&self, entity: &Entity) -> Vec<Entity> {
+        match self.query::<(Entity, &Parent)>() {
+            Ok(query) => query
+                .filter(|(_, parent)| parent.0 == *entity)
+                .map(|(child, _)| child)
+                .collect(),
+            Err(e) => {
+                warn!("Couldn't query children: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    // Parent first, root last. Stops at a despawned parent, a loop ends once every entity was visited
+    pub fn get_ancestors(&self, entity: &Entity) -> Vec<Entity> {
+        let mut ancestors: Vec<Entity> = vec![];
+        let mut current = *entity;
+
+        while ancestors.len() < self.component_storage.get_entities().len() {
+            let parent = match self.component_storage.get_entity_component::<Parent>(&current) {
+                Some(parent) if self.is_alive(&parent.0) => parent.0,
+                _ => break
+            };
+            ancestors.push(parent);
+            current = parent;
+        }
+
+        ancestors
+    }
+
+    // Entities without a Transform don't move their children
+    pub fn get_world_transform(&self, entity: &Entity) -> Transform {
+        let get_transform = |entity: &Entity| self.component_storage.get_entity_component::<Transform>(entity)
+            .map(|transform| transform.clone());
+
+        self.get_ancestors(entity).iter()
+            .filter_map(get_transform)
+            .fold(get_transform(entity).unwrap_or_default(), |transform, parent_transform| transform.with_parent(&parent_transform))
+    }
+
+    pub fn add_prefab(&mut self, prefab: Prefab) -> PrefabHandle {
+        self.prefabs.push(prefab);
+        PrefabHandle(self.prefabs.len() - 1)
+    }
+
+    // The prefab is taken out while it's used so its components can be added to the scene
+    fn with_prefab<R>(&mut self, handle: PrefabHandle, f: impl FnOnce(&mut Scene, &mut Prefab) -> R) -> Option<R> {
+        let mut prefab = match self.prefabs.get_mut(handle.0) {
+            Some(prefab) => std::mem::replace(prefab, Prefab::new("")),
+            None => {
+                warn!("Couldn't find prefab {:?}", handle);
+                return None;
+            }
+        };
+
+        let result = f(self, &mut prefab);
+        self.prefabs[handle.0] = prefab;
+        Some(result)
+    }
+
+    // Spawns the whole subtree and returns its root. The given transform counts as an override of the root,
+    // so moving the prefab doesn't move its instances
+    pub fn spawn_prefab(&mut self, handle: PrefabHandle, transform: Transform, mesh_manager: &mut MeshManager) -> Option<Entity> {
+        self.with_prefab(handle, |scene, prefab| {
+            let mut spawn = PrefabSpawn {
+                handle,
+                mesh_manager,
+                spawned: vec![]
+            };
+            let root = scene.spawn_prefab_node(&mut spawn, prefab, vec![], Some(transform), None);
+
+            if scene.is_set_up {
+                for entity in spawn.spawned.iter() {
+                    scene.setup_script(entity);
+                }
+            }
+            root
+        })
+    }
+
+    fn spawn_prefab_node(&mut self, spawn: &mut PrefabSpawn, prefab: &Prefab, node: Vec<usize>, transform: Option<Transform>,
+        parent: Option<&Entity>) -> Entity {
+        let entity = self.create_entity();
+        self.rename_entity(&entity, prefab.name.clone());
+        prefab.add_to_entity(self, &entity);
+
+        let mut overrides = vec![];
+        if let Some(transform) = transform {
+            match prefab.has_component(TypeId::of::<Transform>()) {
+                true => self.update_entity_component(&entity, transform),
+                false => self.add_component_to_entity(&entity, transform)
+            }
+            overrides.push(TypeId::of::<Transform>());
+        }
+
+        if let Some(parent) = parent {
+            self.add_component_to_entity(&entity, Parent(*parent));
+        }
+
+        if let Some((mesh_index, local_transform)) = prefab.get_mesh() {
+            self.add_mesh_instance(&entity, *mesh_index, local_transform, spawn.mesh_manager);
+        }
+
+        self.add_component_to_entity(&entity, PrefabInstance {
+            handle: spawn.handle,
+            node: node.clone(),
+            overrides
+        });
+        spawn.spawned.push(entity);
+
+        for (child_index, child) in prefab.get_children().iter().enumerate() {
+            let mut child_node = node.clone();
+            child_node.push(child_index);
+            self.spawn_prefab_node(spawn, child, child_node, None, Some(&entity));
+        }
+
+        entity
+    }
+
+    fn add_mesh_instance(&mut self, entity: &Entity, mesh_index: usize, local_transform: &Transform, mesh_manager: &mut MeshManager) {
+        match mesh_manager.create_mesh_instance(mesh_index) {
+            Some(mesh_instance_index) => self.add_component_to_entity(entity, MeshInstance {
+                mesh_index,
+                mesh_instance_index,
+                local_transform: local_transform.clone()
+            }),
+            None => warn!("Couldn't create an instance of mesh {}", mesh_index)
+        }
+    }
+
+    // Entities spawned from the prefab node that didn't override T
+    fn get_prefab_instances<T: 'static>(&self, handle: PrefabHandle, node: &[usize]) -> Vec<Entity> {
+        match self.query::<(Entity, &PrefabInstance)>() {
+            Ok(query) => query
+                .filter(|(_, prefab_instance)| prefab_instance.handle == handle && prefab_instance.node == node && !prefab_instance.is_overridden::<T>())
+                .map(|(entity, _)| entity)
+                .collect(),
+            Err(e) => {
+                warn!("Couldn't query prefab instances: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    // Changes a component on one prefab instance and keeps it from being reset by prefab edits
+    pub fn override_component<T: Component + Clone + 'static>(&mut self, entity: &Entity, component: T) {
+        self.update_entity_component(entity, component);
+
+        if let Some(mut prefab_instance) = self.component_storage.get_entity_component_mut::<PrefabInstance>(entity) {
+            if !prefab_instance.is_overridden::<T>() {
+                prefab_instance.overrides.push(TypeId::of::<T>());
+            }
+        }
+    }
+
+    // Updates the prefab root and every instance that didn't override the component
+    pub fn set_prefab_component<T: Component + Clone + 'static>(&mut self, handle: PrefabHandle, component: T) {
+        self.set_prefab_node_component(handle, &[], component);
+    }
+
+    // node is the child indices leading to a prefab child, see PrefabInstance::node
+    pub fn set_prefab_node_component<T: Component + Clone + 'static>(&mut self, handle: PrefabHandle, node: &[usize], component: T) {
+        let instances = self.get_prefab_instances::<T>(handle, node);
+
+        self.with_prefab(handle, |scene, prefab| {
+            let prefab_node = match prefab.get_node_mut(node) {
+                Some(prefab_node) => prefab_node,
+                None => {
+                    warn!("Prefab {} has no node {:?}", prefab.name, node);
+                    return;
+                }
+            };
+
+            prefab_node.set_component(component);
+            for entity in instances.iter() {
+                prefab_node.apply_component_to_entity(TypeId::of::<T>(), scene, entity);
+            }
+        });
+    }
+
+    // Instances that didn't override their MeshInstance get a renderer instance of the new mesh,
+    // the old one is freed after the next extract
+    pub fn set_prefab_mesh(&mut self, handle: PrefabHandle, node: &[usize], mesh: Option<(usize, Transform)>, mesh_manager: &mut MeshManager) {
+        let instances = self.get_prefab_instances::<MeshInstance>(handle, node);
+
+        self.with_prefab(handle, |scene, prefab| {
+            match prefab.get_node_mut(node) {
+                Some(prefab_node) => prefab_node.set_mesh(mesh.clone()),
+                None => {
+                    warn!("Prefab {} has no node {:?}", prefab.name, node);
+                    return;
+                }
+            }
+
+            for entity in instances.iter() {
+                let current_mesh_index = scene.component_storage.get_entity_component::<MeshInstance>(entity)
+                    .map(|mesh_instance| mesh_instance.mesh_index);
+
+                match (&mesh, current_mesh_index) {
+                    (Some((mesh_index, local_transform)), Some(current_mesh_index)) if *mesh_index == current_mesh_index => {
+                        if let Some(mut mesh_instance) = scene.get_component_mut::<MeshInstance>(entity) {
+                            mesh_instance.local_transform = local_transform.clone();
+                        }
+                    },
+                    (Some((mesh_index, local_transform)), _) => {
+                        scene.remove_component::<MeshInstance>(entity);
+                        scene.add_mesh_instance(entity, *mesh_index, local_transform, mesh_manager);
+                    },
+                    (None, _) => {
+                        scene.remove_component::<MeshInstance>(entity);
+                    }
+                }
+            }
+        });
+    }
 
     pub fn add_script_to_entity<T: Script + 'static>(&mut self, entity: &Entity, script: T) {
-        let mut boxed_script: Box<dyn Script> = Box::new(script);
-        boxed_script.pre_setup(entity.clone(), &mut self.component_storage);
+        self.add_boxed_script_to_entity(entity, Box::new(script));
+    }
+
+    // Scripts are registered by #[derive(RegisterScript)], returns false for unknown and duplicated names.
+    // With hot_reload, names the engine doesn't know are looked up in the gameplay library
+    pub fn add_script_by_name(&mut self, entity: &Entity, name: &str) -> bool {
+        match script_registry::create_script(name) {
+            Ok(script) => {
+                self.add_boxed_script_to_entity(entity, script);
+                true
+            },
+            #[cfg(feature = "hot_reload")]
+            Err(script_registry::ScriptRegistryError::NotFound(_)) if self.resource::<crate::hot_reload::GameplayLibrary>().is_some() => {
+                crate::hot_reload::add_library_script(self, entity, name)
+            },
+            Err(e) => {
+                warn!("Couldn't add script to entity {}: {}", entity, e);
+                false
+            }
+        }
+    }
+
+    // For scripts added after Scene::setup_components already ran
+    pub fn setup_script(&self, entity: &Entity) {
+        if let Some(mut script) = self.component_storage.get_entity_component_mut::<Box<dyn Script>>(entity) {
+            self.with_script_context(|context| script.run_hook(&self.component_storage, |script| script.script_setup(context)));
+        }
+    }
+
+    pub fn add_boxed_script_to_entity(&mut self, entity: &Entity, mut boxed_script: Box<dyn Script>) {
+        boxed_script.pre_setup(*entity, &mut self.component_storage);
         boxed_script.post_user_update(&self.component_storage);
-        self.add_component_to_entity(entity, boxed_script);
+        self.component_storage.register_component(entity, boxed_script);
+        self.component_storage.register_component(entity, ScriptEnabled::default());
+    }
+
+    // Calls on_enable/on_disable only when the state actually changes
+    pub fn set_script_enabled(&self, entity: &Entity, enabled: bool) {
+        match self.get_component_mut::<ScriptEnabled>(entity) {
+            Some(mut script_enabled) if script_enabled.0 != enabled => script_enabled.0 = enabled,
+            Some(_) => return,
+            None => {
+                warn!("Entity {} doesn't have a script", entity);
+                return;
+            }
+        }
+
+        if let Some(mut script) = self.component_storage.get_entity_component_mut::<Box<dyn Script>>(entity) {
+            self.with_script_context(|context| script.run_hook(&self.component_storage, |script| match enabled {
+                true => script.on_enable(context),
+                false => script.on_disable(context)
+            }));
+        }
+    }
+
+    fn destroy_script(&self, entity: &Entity) {
+        if let Some(mut script) = self.component_storage.get_entity_component_mut::<Box<dyn Script>>(entity) {
+            self.with_script_context(|context| script.run_hook(&self.component_storage, |script| script.on_destroy(context)));
+        }
+    }
+
+    fn with_script_context(&self, f: impl FnOnce(&ScriptContext)) {
+        match self.resource::<Time>() {
+            Some(time) => f(&ScriptContext::new(self, &time)),
+            None => warn!("Scripts can't run without the Time resource")
+        }
+    }
+
+    fn for_each_script(&self, mut f: impl FnMut(&mut Box<dyn Script>)) {
+        match self.query::<&mut Box<dyn Script>>() {
+            Ok(query) => for mut script in query {
+                f(&mut script);
+            },
+            Err(e) => warn!("Couldn't query scripts: {}", e)
+        }
+    }
+
+    fn run_entity_script_hook(&self, entity: &Entity, context: &ScriptContext, hook: fn(&mut dyn Script, &ScriptContext)) {
+        if let Some(mut script) = self.component_storage.get_entity_component_mut::<Box<dyn Script>>(entity) {
+            if script.is_enabled(&self.component_storage) {
+                script.run_hook(&self.component_storage, |script| hook(script, context));
+            }
+        }
+    }
+
+    // Runs a hook on every enabled script. Independent scripts run first on the thread pool, the others one by one after them
+    pub fn run_script_hook(&self, hook: fn(&mut dyn Script, &ScriptContext)) {
+        let time = match self.resource::<Time>() {
+            Some(time) => time,
+            None => {
+                warn!("Scripts can't run without the Time resource");
+                return;
+            }
+        };
+
+        let mut independent = vec![];
+        let mut others = vec![];
+        match self.query::<(Entity, &Box<dyn Script>)>() {
+            Ok(query) => for (entity, script) in query {
+                match script.is_independent() {
+                    true => independent.push(entity),
+                    false => others.push(entity)
+                }
+            },
+            Err(e) => {
+                warn!("Couldn't query scripts: {}", e);
+                return;
+            }
+        }
+
+        independent.par_iter().for_each(|entity| {
+            self.run_entity_script_hook(entity, &ScriptContext::new_independent(self, &time, *entity), hook);
+        });
+        let context = ScriptContext::new(self, &time);
+        for entity in others {
+            self.run_entity_script_hook(&entity, &context, hook);
+        }
+    }
+
+    // Disabled scripts get on_destroy too
+    pub fn destroy_scripts(&self) {
+        self.with_script_context(|context| self.for_each_script(|script| {
+            script.run_hook(&self.component_storage, |script| script.on_destroy(context));
+        }));
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use script_gen_macro::Reflect;
+
+    use crate::{entities::components::{LodLevel, LodThreshold}, script::ScriptComponentUpdater};
+    use super::*;
+
+    fn lod_group() -> LodGroup {
+        let level = |mesh_index, mesh_instance_index| LodLevel { mesh_index, mesh_instance_index, threshold: LodThreshold::Distance(10.0) };
+        LodGroup {
+            levels: vec![level(0, 3), level(1, 5)],
+            cross_fade: None
+        }
+    }
+
+    fn freed_instances(scene: &Scene) -> Vec<(usize, usize)> {
+        scene.take_removed_mesh_instances().iter()
+            .map(|mesh_instance| (mesh_instance.mesh_index, mesh_instance.mesh_instance_index))
+            .collect()
+    }
+
+    #[test]
+    fn despawn_frees_lod_instances() {
+        let mut scene = Scene::new();
+        let entity = scene.create_entity();
+        scene.add_component_to_entity(&entity, lod_group());
+        scene.despawn(&entity);
+        assert_eq!(freed_instances(&scene), vec![(0, 3), (1, 5)]);
+    }
+
+    #[test]
+    fn removing_a_lod_group_frees_its_instances() {
+        let mut scene = Scene::new();
+        let entity = scene.create_entity();
+        scene.add_component_to_entity(&entity, lod_group());
+        scene.remove_component::<LodGroup>(&entity);
+        assert_eq!(freed_instances(&scene), vec![(0, 3), (1, 5)]);
+    }
+
+    // Reads the Name of its own entity and of another one
+    #[derive(Reflect)]
+    struct NameReader {
+        entity: Entity,
+        other: Entity,
+        #[reflect(skip)]
+        names_read: Arc<AtomicU32>
+    }
+
+    impl ScriptComponentUpdater for NameReader {
+        fn get_entity(&self) -> Entity {
+            self.entity
+        }
+        fn pre_setup(&mut self, entity: Entity, _world: &mut ComponentStorage) {
+            self.entity = entity;
+        }
+        fn pre_user_update(&mut self, _world: &ComponentStorage) {}
+        fn post_user_update(&mut self, _world: &ComponentStorage) {}
+    }
+
+    impl Script for NameReader {
+        fn script_setup(&mut self, _context: &ScriptContext) {}
+        fn script_update(&mut self, context: &ScriptContext) {
+            for entity in [self.entity, self.other] {
+                if context.get_component::<Name>(&entity).is_some() {
+                    self.names_read.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        fn is_independent(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn independent_scripts_only_reach_their_own_entity() {
+        let mut scene = Scene::new();
+        scene.insert_resource(Time::new(0.02));
+        let names_read = Arc::new(AtomicU32::new(0));
+        let entities: Vec<Entity> = (0..2).map(|_| scene.create_entity()).collect();
+        for (entity, other) in [(entities[0], entities[1]), (entities[1], entities[0])] {
+            let script = NameReader { entity: Entity::default(), other, names_read: names_read.clone() };
+            scene.add_boxed_script_to_entity(&entity, Box::new(script));
+        }
+
+        scene.run_script_hook(|script, context| script.script_update(context));
+        assert_eq!(names_read.load(Ordering::Relaxed), 2);
     }
 }
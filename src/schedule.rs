@@ -0,0 +1,293 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{scene::Scene, query::{Access, QueryParam}, topological_sort::topological_sort};
+
+// Stages run in this order every frame, systems can only be ordered against others in the same stage.
+// FixedUpdate runs once per fixed step, which can be zero or several times in a frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    PreUpdate,
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    RenderExtract
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [Stage::PreUpdate, Stage::FixedUpdate, Stage::Update, Stage::PostUpdate, Stage::RenderExtract];
+}
+
+// Change ticks of a system run, pass last_run to Scene::query_filtered_since so Added and Changed
+// match what happened since the system's previous run
+#[derive(Clone, Copy, Debug)]
+pub struct SystemTicks {
+    pub last_run: u32,
+    pub this_run: u32
+}
+
+// Systems in a batch run on the rayon thread pool
+pub type SystemFn = dyn FnMut(&Scene, SystemTicks) + Send;
+
+pub struct System {
+    name: &'static str,
+    last_run: u32,
+    stage: Stage,
+    after: Vec<&'static str>,
+    before: Vec<&'static str>,
+    // None until something is declared, such a system conflicts with every other one
+    accesses: Option<Vec<Access>>,
+    run: Box<SystemFn>
+}
+
+impl System {
+    pub fn new(name: &'static str, stage: Stage, run: impl FnMut(&Scene, SystemTicks) + Send + 'static) -> Self {
+        Self {
+            name,
+            last_run: 0,
+            stage,
+            after: vec![],
+            before: vec![],
+            accesses: None,
+            run: Box::new(run)
+        }
+    }
+
+    // Components and resources the system touches, used to find systems that can share a batch
+    pub fn with_query<Q: for<'w> QueryParam<'w>>(mut self) -> Self {
+        Q::access(self.accesses.get_or_insert_with(Vec::new));
+        self
+    }
+
+    pub fn reads<T: 'static>(mut self) -> Self {
+        self.accesses.get_or_insert_with(Vec::new).push(Access::of::<T>(false));
+        self
+    }
+
+    pub fn writes<T: 'static>(mut self) -> Self {
+        self.accesses.get_or_insert_with(Vec::new).push(Access::of::<T>(true));
+        self
+    }
+
+    fn conflicts_with(&self, other: &System) -> bool {
+        match (&self.accesses, &other.accesses) {
+            (Some(accesses), Some(other_accesses)) => accesses.iter()
+                .any(|access| other_accesses.iter().any(|other_access| access.conflicts_with(other_access))),
+            _ => true
+        }
+    }
+
+    fn is_ordered_with(&self, other: &System) -> bool {
+        self.after.contains(&other.name) || self.before.contains(&other.name)
+            || other.after.contains(&self.name) || other.before.contains(&self.name)
+    }
+
+    // The named system has to be registered first, in the same stage
+    pub fn after(mut self, name: &'static str) -> Self {
+        self.after.push(name);
+        self
+    }
+
+    pub fn before(mut self, name: &'static str) -> Self {
+        self.before.push(name);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum ScheduleError {
+    DuplicateSystem(&'static str),
+    UnknownSystem { system: &'static str, dependency: &'static str },
+    CrossStageDependency { system: &'static str, dependency: &'static str },
+    CyclicDependency(Vec<&'static str>)
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::DuplicateSystem(name) => write!(f, "System {} is already registered", name),
+            ScheduleError::UnknownSystem { system, dependency } => write!(f, "System {} is ordered against {}, which isn't registered", system, dependency),
+            ScheduleError::CrossStageDependency { system, dependency } => write!(f, "System {} is ordered against {}, which runs in another stage", system, dependency),
+            ScheduleError::CyclicDependency(systems) => write!(f, "Schedule has a cycle between systems: {:?}", systems)
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+pub struct Schedule {
+    systems: Vec<System>,
+    // Systems in a batch don't conflict and aren't ordered against each other
+    batches: Vec<Vec<usize>>
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self {
+            systems: vec![],
+            batches: vec![]
+        }
+    }
+
+    // The order is rebuilt on every add, a system that would close a cycle doesn't get added
+    pub fn add_system(&mut self, system: System) -> Result<(), ScheduleError> {
+        if self.systems.iter().any(|other| other.name == system.name) {
+            return Err(ScheduleError::DuplicateSystem(system.name));
+        }
+        for dependency in system.after.iter().chain(system.before.iter()).copied() {
+            match self.systems.iter().find(|other| other.name == dependency) {
+                Some(other) if other.stage != system.stage => return Err(ScheduleError::CrossStageDependency { system: system.name, dependency }),
+                Some(_) => {},
+                None => return Err(ScheduleError::UnknownSystem { system: system.name, dependency })
+            }
+        }
+
+        self.systems.push(system);
+        match self.sort_systems() {
+            Ok(order) => {
+                self.batches = self.get_batches(&order);
+                Ok(())
+            },
+            Err(e) => {
+                self.systems.pop();
+                Err(e)
+            }
+        }
+    }
+
+    fn get_dependencies(&self, stage_systems: &[usize]) -> Vec<Vec<usize>> {
+        stage_systems.iter()
+            .map(|system_index| {
+                let system = &self.systems[*system_index];
+                stage_systems.iter()
+                    .enumerate()
+                    .filter(|(_, other_index)| *other_index != system_index)
+                    .filter(|(_, other_index)| {
+                        let other = &self.systems[**other_index];
+                        system.after.contains(&other.name) || other.before.contains(&system.name)
+                    })
+                    .map(|(position, _)| position)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn sort_systems(&self) -> Result<Vec<usize>, ScheduleError> {
+        let mut order = Vec::with_capacity(self.systems.len());
+
+        for stage in Stage::ALL {
+            let stage_systems: Vec<usize> = (0..self.systems.len())
+                .filter(|index| self.systems[*index].stage == stage)
+                .collect();
+            // Positions in stage_systems, the sort keeps the registration order whenever possible
+            match topological_sort(&self.get_dependencies(&stage_systems)) {
+                Ok(positions) => order.extend(positions.iter().map(|position| stage_systems[*position])),
+                Err(unsorted) => {
+                    let names = unsorted.iter().map(|position| self.systems[stage_systems[*position]].name).collect();
+                    return Err(ScheduleError::CyclicDependency(names));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    // Walks the sorted order and starts a new batch whenever a system can't join the current one
+    fn get_batches(&self, order: &[usize]) -> Vec<Vec<usize>> {
+        let mut batches: Vec<Vec<usize>> = vec![];
+
+        for system_index in order.iter() {
+            let system = &self.systems[*system_index];
+            let can_join = batches.last().is_some_and(|batch: &Vec<usize>| batch.iter().all(|other_index| {
+                let other = &self.systems[*other_index];
+                other.stage == system.stage && !system.conflicts_with(other) && !system.is_ordered_with(other)
+            }));
+
+            match batches.last_mut() {
+                Some(batch) if can_join => batch.push(*system_index),
+                _ => batches.push(vec![*system_index])
+            }
+        }
+
+        batches
+    }
+
+    pub fn get_batch_names(&self) -> Vec<Vec<&'static str>> {
+        self.batches.iter()
+            .map(|batch| batch.iter().map(|system_index| self.systems[*system_index].name).collect())
+            .collect()
+    }
+
+    // Systems of a batch run in parallel, the next batch starts once they're all done.
+    // Component and resource borrows are checked at runtime, so a wrong access declaration panics
+    pub fn run(&mut self, scene: &Scene, fixed_steps: u32) {
+        for batch in self.batches.iter() {
+            // Batches never mix stages
+            let runs = match self.systems[batch[0]].stage {
+                Stage::FixedUpdate => fixed_steps,
+                _ => 1
+            };
+
+            for _ in 0..runs {
+                match batch.as_slice() {
+                    [system_index] => Self::run_system(&mut self.systems[*system_index], scene),
+                    _ => rayon::scope(|scope| {
+                        for (_, system) in self.systems.iter_mut().enumerate().filter(|(index, _)| batch.contains(index)) {
+                            scope.spawn(move |_| Self::run_system(system, scene));
+                        }
+                    })
+                }
+            }
+        }
+    }
+
+    fn run_system(system: &mut System, scene: &Scene) {
+        let ticks = SystemTicks {
+            last_run: system.last_run,
+            this_run: scene.component_storage.increment_change_tick()
+        };
+        (system.run)(scene, ticks);
+        system.last_run = ticks.this_run;
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(name: &'static str) -> System {
+        System::new(name, Stage::Update, |_, _| {})
+    }
+
+    #[test]
+    fn unknown_dependency_is_an_error() {
+        let mut schedule = Schedule::new();
+        let result = schedule.add_system(system("a").after("missing"));
+        assert!(matches!(result, Err(ScheduleError::UnknownSystem { system: "a", dependency: "missing" })));
+        assert!(schedule.systems.is_empty());
+    }
+
+    #[test]
+    fn dependency_in_another_stage_is_an_error() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(System::new("extract", Stage::RenderExtract, |_, _| {})).unwrap();
+        let result = schedule.add_system(system("a").after("extract"));
+        assert!(matches!(result, Err(ScheduleError::CrossStageDependency { system: "a", dependency: "extract" })));
+        assert_eq!(schedule.get_batch_names(), vec![vec!["extract"]]);
+    }
+
+    #[test]
+    fn cycle_is_rejected() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(system("a")).unwrap();
+        schedule.add_system(system("b").after("a")).unwrap();
+        let result = schedule.add_system(system("c").after("b").before("a"));
+        assert!(matches!(result, Err(ScheduleError::CyclicDependency(_))));
+        assert_eq!(schedule.get_batch_names(), vec![vec!["a"], vec!["b"]]);
+    }
+}
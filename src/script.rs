@@ -1,34 +1,186 @@
 
-use probable_spork_ecs::{component::{Entity, Component, ComponentStorage}};
+use std::any::Any;
+
+use log::warn;
+use probable_spork_ecs::{component::{Entity, Component, ComponentStorage}, change_detection::Mut, AtomicRef};
+
+use crate::{reflect::Reflect, entities::components::ScriptEnabled, time::Time, scene::Scene, query::{Query, QueryParam, QueryError}, commands::EntityBuilder};
 
 pub trait ScriptComponentUpdater {
+    fn get_entity(&self) -> Entity;
     fn pre_setup(&mut self, entity: Entity, world: &mut ComponentStorage);
     fn pre_user_update(&mut self, world: &ComponentStorage);
     fn post_user_update(&mut self, world: &ComponentStorage);
 }
 
-pub trait Script: ScriptComponentUpdater{
-    fn script_setup(&mut self);
-    fn script_update(&mut self);
+// Passed to every script hook. Components synced into the script are written back after the hook,
+// so changing them here gets overwritten, and the script's own component can't be borrowed here
+pub struct ScriptContext<'a> {
+    pub time: &'a Time,
+    scene: &'a Scene,
+    // Entity of the independent script using the context, see Script::is_independent
+    independent_entity: Option<Entity>
 }
 
-impl PartialEq for Box<dyn Script> {
-    fn eq(&self, _other: &Self) -> bool {
-        false
+impl<'a> ScriptContext<'a> {
+    pub fn new(scene: &'a Scene, time: &'a Time) -> Self {
+        Self {
+            time,
+            scene,
+            independent_entity: None
+        }
+    }
+
+    // Other independent scripts run at the same time, so this context only reaches the script's own entity
+    pub fn new_independent(scene: &'a Scene, time: &'a Time, entity: Entity) -> Self {
+        Self {
+            time,
+            scene,
+            independent_entity: Some(entity)
+        }
+    }
+
+    fn can_access(&self, entity: &Entity) -> bool {
+        match self.independent_entity {
+            Some(own) if own != *entity => {
+                warn!("Independent script on entity {} can't access entity {}", own, entity);
+                false
+            },
+            _ => true
+        }
+    }
+
+    // None once the entity is despawned, the generation of the handle doesn't match anymore
+    pub fn get_component<T: Component + 'static>(&self, entity: &Entity) -> Option<AtomicRef<'a, T>> {
+        if !self.can_access(entity) {
+            return None;
+        }
+        self.scene.component_storage.get_entity_component::<T>(entity)
+    }
+
+    pub fn get_component_mut<T: Component + 'static>(&self, entity: &Entity) -> Option<Mut<'a, T>> {
+        if !self.can_access(entity) {
+            return None;
+        }
+        self.scene.get_component_mut::<T>(entity)
+    }
+
+    // Replaces the component right away, adding a missing one is deferred to the end of the frame
+    pub fn set_component<T: Component + 'static>(&self, entity: &Entity, component: T) {
+        if !self.can_access(entity) {
+            return;
+        }
+        match self.scene.get_component_mut::<T>(entity) {
+            Some(mut current) => *current = component,
+            None => self.scene.get_commands_mut().insert(entity, component)
+        }
+    }
+
+    pub fn query<Q: QueryParam<'a>>(&self) -> Result<Query<'a, Q>, QueryError> {
+        match self.independent_entity {
+            Some(_) => Err(QueryError::IndependentScript),
+            None => self.scene.query::<Q>()
+        }
+    }
+
+    // Always None for independent scripts
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        match self.independent_entity {
+            Some(own) => {
+                warn!("Independent script on entity {} can't look up {}", own, name);
+                None
+            },
+            None => self.scene.find_by_name(name)
+        }
+    }
+
+    pub fn resource<T: 'static>(&self) -> Option<AtomicRef<'a, T>> {
+        self.scene.resource::<T>()
     }
-    fn ne(&self, _other: &Self) -> bool {
+
+    // Events from independent scripts are sent at the end of the frame
+    pub fn send_event<T: Send + 'static>(&self, event: T) {
+        match self.independent_entity {
+            Some(_) => self.scene.get_commands_mut().send_event(event),
+            None => self.scene.send_event(event)
+        }
+    }
+
+    // Deferred to the end of the frame
+    pub fn spawn(&self, builder: EntityBuilder) {
+        self.scene.get_commands_mut().spawn(builder);
+    }
+
+    // Deferred to the end of the frame
+    pub fn remove_component<T: Component + 'static>(&self, entity: &Entity) {
+        self.scene.get_commands_mut().remove::<T>(entity);
+    }
+
+    // Deferred to the end of the frame
+    pub fn despawn(&self, entity: &Entity) {
+        self.scene.get_commands_mut().despawn(entity);
+    }
+}
+
+// Send + Sync like every other component, systems run on a thread pool.
+// Reflect is derived, it lets the editor inspect scripts and hot reload carry their state over
+pub trait Script: ScriptComponentUpdater + Reflect + Send + Sync {
+    fn script_setup(&mut self, context: &ScriptContext);
+    fn script_update(&mut self, context: &ScriptContext);
+
+    // Independent scripts only touch their own entity, so their hooks run in parallel with each other.
+    // Their context can't query or reach other entities, see ScriptContext::new_independent
+    fn is_independent(&self) -> bool {
         false
     }
+
+    // Runs zero or more times per frame, context.time.fixed_delta apart
+    fn fixed_update(&mut self, _context: &ScriptContext) {
+    }
+    // Runs after every script's update, before the render extract applies the transforms
+    fn late_update(&mut self, _context: &ScriptContext) {
+    }
+    fn on_enable(&mut self, _context: &ScriptContext) {
+    }
+    fn on_disable(&mut self, _context: &ScriptContext) {
+    }
+    // Runs once, when the entity is despawned, the script is removed or the scene is torn down
+    fn on_destroy(&mut self, _context: &ScriptContext) {
+    }
 }
 
-impl Component for Box<dyn Script> {
-    fn setup(&mut self, world: &ComponentStorage) {
-        self.script_setup();
-        self.post_user_update(world);
+// Generic component paths use this to send a Box<dyn Script> through Scene::add_boxed_script_to_entity
+pub fn into_script<T: 'static>(component: T) -> Result<Box<dyn Script>, T> {
+    let mut component = Some(component);
+    match (&mut component as &mut dyn Any).downcast_mut::<Option<Box<dyn Script>>>() {
+        Some(script) => Ok(script.take().expect("Component was just wrapped")),
+        None => Err(component.expect("Component was just wrapped"))
     }
-    fn update(&mut self, world: &ComponentStorage) {
+}
+
+impl dyn Script {
+    // Synced components are copied in before the hook and written back after it
+    pub fn run_hook(&mut self, world: &ComponentStorage, hook: impl FnOnce(&mut dyn Script)) {
         self.pre_user_update(world);
-        self.script_update();
+        hook(self);
         self.post_user_update(world);
     }
+
+    pub fn is_enabled(&self, world: &ComponentStorage) -> bool {
+        world.get_entity_component::<ScriptEnabled>(&self.get_entity()).is_none_or(|enabled| enabled.0)
+    }
+}
+
+impl PartialEq for Box<dyn Script> {
+    fn eq(&self, _other: &Self) -> bool {
+        false
+    }
+}
+
+// Scripts need scene resources the component update can't pass, the Scene drives them instead
+impl Component for Box<dyn Script> {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
 }
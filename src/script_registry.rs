@@ -0,0 +1,116 @@
+use std::{error::Error, fmt::Display};
+
+use crate::script::Script;
+
+// Used by the RegisterScript derive, so crates using it don't need their own inventory dependency
+pub use inventory;
+
+// Submitted by #[derive(RegisterScript)] for every script type that implements Default
+pub struct ScriptRegistration {
+    pub name: &'static str,
+    // Full path of the type, tells apart scripts that share a name
+    pub type_path: &'static str,
+    pub create: fn() -> Box<dyn Script>
+}
+
+inventory::collect!(ScriptRegistration);
+
+#[derive(Debug)]
+pub enum ScriptRegistryError {
+    NotFound(String),
+    // Script names are the type names, two types with the same name can't be created by name
+    Duplicate(String, Vec<&'static str>)
+}
+
+impl Display for ScriptRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptRegistryError::NotFound(name) => write!(f, "No script registered as {}", name),
+            ScriptRegistryError::Duplicate(name, type_paths) => write!(f, "Script name {} is registered by several types: {:?}", name, type_paths)
+        }
+    }
+}
+
+impl Error for ScriptRegistryError {}
+
+fn get_registrations(name: &str) -> Vec<&'static ScriptRegistration> {
+    inventory::iter::<ScriptRegistration>
+        .into_iter()
+        .filter(|registration| registration.name == name)
+        .collect()
+}
+
+// Duplicated names are left out, create_script refuses them
+pub fn get_script_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = inventory::iter::<ScriptRegistration>
+        .into_iter()
+        .map(|registration| registration.name)
+        .filter(|name| get_registrations(name).len() == 1)
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn create_script(name: &str) -> Result<Box<dyn Script>, ScriptRegistryError> {
+    match get_registrations(name).as_slice() {
+        [registration] => Ok((registration.create)()),
+        [] => Err(ScriptRegistryError::NotFound(name.to_string())),
+        registrations => Err(ScriptRegistryError::Duplicate(name.to_string(), registrations.iter().map(|registration| registration.type_path).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two script types with the same name in different modules
+    mod first {
+        use probable_spork_ecs::component::Entity;
+        use script_gen_macro::{ScriptComponentUpdater, Reflect, RegisterScript};
+
+        use crate::script::{Script, ScriptComponentUpdater, ScriptContext};
+
+        #[derive(ScriptComponentUpdater, Reflect, RegisterScript, Default)]
+        pub struct DuplicateScript {
+            entity: Entity
+        }
+
+        impl Script for DuplicateScript {
+            fn script_setup(&mut self, _context: &ScriptContext) {}
+            fn script_update(&mut self, _context: &ScriptContext) {}
+        }
+    }
+
+    mod second {
+        use probable_spork_ecs::component::Entity;
+        use script_gen_macro::{ScriptComponentUpdater, Reflect, RegisterScript};
+
+        use crate::script::{Script, ScriptComponentUpdater, ScriptContext};
+
+        #[derive(ScriptComponentUpdater, Reflect, RegisterScript, Default)]
+        pub struct DuplicateScript {
+            entity: Entity
+        }
+
+        impl Script for DuplicateScript {
+            fn script_setup(&mut self, _context: &ScriptContext) {}
+            fn script_update(&mut self, _context: &ScriptContext) {}
+        }
+    }
+
+    #[test]
+    fn registered_script_is_created() {
+        assert!(get_script_names().contains(&"TestScript"));
+        assert!(create_script("TestScript").is_ok());
+        assert!(matches!(create_script("MissingScript"), Err(ScriptRegistryError::NotFound(_))));
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        assert!(!get_script_names().contains(&"DuplicateScript"));
+        match create_script("DuplicateScript") {
+            Err(ScriptRegistryError::Duplicate(_, type_paths)) => assert_eq!(type_paths.len(), 2),
+            _ => panic!("Expected a duplicate error")
+        }
+    }
+}
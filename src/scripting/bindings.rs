@@ -0,0 +1,70 @@
+use cgmath::{Vector3, Quaternion, Euler, Deg};
+use probable_spork_ecs::component::Entity;
+use rhai::{Engine, Dynamic, Map};
+
+use crate::{entities::components::{Transform, MeshInstance}, time::Time, input::Input};
+
+// What a script sees as `this`, copied out of the scene before every call and written back after it.
+// Components the entity doesn't have read as () and setting them does nothing
+#[derive(Clone)]
+pub struct ScriptEntity {
+    pub entity: Entity,
+    pub transform: Option<Transform>,
+    pub mesh: Option<MeshInstance>,
+    // Script owned values, kept between calls and across reloads
+    pub state: Map
+}
+
+fn to_dynamic<T: Clone + Send + Sync + 'static>(value: &Option<T>) -> Dynamic {
+    value.clone().map_or(Dynamic::UNIT, Dynamic::from)
+}
+
+pub fn register_bindings(engine: &mut Engine) {
+    engine.register_type_with_name::<Vector3<f32>>("Vec3")
+        .register_fn("vec3", |x: f32, y: f32, z: f32| Vector3::new(x, y, z))
+        .register_get_set("x", |v: &mut Vector3<f32>| v.x, |v: &mut Vector3<f32>, x: f32| v.x = x)
+        .register_get_set("y", |v: &mut Vector3<f32>| v.y, |v: &mut Vector3<f32>, y: f32| v.y = y)
+        .register_get_set("z", |v: &mut Vector3<f32>| v.z, |v: &mut Vector3<f32>, z: f32| v.z = z)
+        .register_fn("+", |a: Vector3<f32>, b: Vector3<f32>| a + b)
+        .register_fn("-", |a: Vector3<f32>, b: Vector3<f32>| a - b)
+        .register_fn("*", |v: Vector3<f32>, scale: f32| v * scale)
+        .register_fn("to_string", |v: &mut Vector3<f32>| format!("({}, {}, {})", v.x, v.y, v.z));
+
+    // Angles are in degrees
+    engine.register_type_with_name::<Quaternion<f32>>("Quat")
+        .register_fn("quat_from_euler", |x: f32, y: f32, z: f32| Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))))
+        .register_fn("*", |a: Quaternion<f32>, b: Quaternion<f32>| a * b);
+
+    engine.register_type_with_name::<Transform>("Transform")
+        .register_get_set("position", |t: &mut Transform| t.position, |t: &mut Transform, position: Vector3<f32>| t.position = position)
+        .register_get_set("rotation", |t: &mut Transform| t.rotation, |t: &mut Transform, rotation: Quaternion<f32>| t.rotation = rotation);
+
+    engine.register_type_with_name::<MeshInstance>("MeshInstance")
+        .register_get("mesh_index", |m: &mut MeshInstance| m.mesh_index as rhai::INT)
+        .register_get_set("local_transform", |m: &mut MeshInstance| m.local_transform.clone(), |m: &mut MeshInstance, t: Transform| m.local_transform = t);
+
+    engine.register_type_with_name::<Time>("Time")
+        .register_get("delta", |time: &mut Time| time.delta)
+        .register_get("unscaled_delta", |time: &mut Time| time.unscaled_delta)
+        .register_get("elapsed", |time: &mut Time| time.elapsed as f32)
+        .register_get("frame_count", |time: &mut Time| time.frame_count as rhai::INT);
+
+    engine.register_type_with_name::<Input>("Input")
+        .register_fn("is_pressed", |input: &mut Input, key: &str| input.is_pressed_by_name(key));
+
+    engine.register_type_with_name::<ScriptEntity>("Entity")
+        .register_get("id", |e: &mut ScriptEntity| e.entity.index() as rhai::INT)
+        .register_get("transform", |e: &mut ScriptEntity| to_dynamic(&e.transform))
+        .register_set("transform", |e: &mut ScriptEntity, t: Transform| {
+            if e.transform.is_some() {
+                e.transform = Some(t);
+            }
+        })
+        .register_get("mesh", |e: &mut ScriptEntity| to_dynamic(&e.mesh))
+        .register_set("mesh", |e: &mut ScriptEntity, m: MeshInstance| {
+            if e.mesh.is_some() {
+                e.mesh = Some(m);
+            }
+        })
+        .register_get_set("state", |e: &mut ScriptEntity| e.state.clone(), |e: &mut ScriptEntity, state: Map| e.state = state);
+}
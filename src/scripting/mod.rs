@@ -0,0 +1,90 @@
+mod bindings;
+mod scripted_behaviour;
+
+pub use bindings::ScriptEntity;
+pub use scripted_behaviour::ScriptedBehaviour;
+
+use std::{collections::HashMap, time::SystemTime};
+
+use log::warn;
+use probable_spork_ecs::component::Entity;
+
+use crate::{scene::Scene, schedule::SystemTicks, time::Time, input::Input, entities::components::{Transform, MeshInstance, ScriptEnabled}};
+
+// Rhai engine shared by every ScriptedBehaviour, stored as a scene resource
+pub struct ScriptingEngine {
+    engine: rhai::Engine
+}
+
+impl ScriptingEngine {
+    pub fn new() -> Self {
+        let mut engine = rhai::Engine::new();
+        bindings::register_bindings(&mut engine);
+
+        Self {
+            engine
+        }
+    }
+}
+
+impl Default for ScriptingEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Only writes components the script actually changed, so change detection stays accurate
+fn write_back<T: probable_spork_ecs::component::Component + PartialEq + 'static>(scene: &Scene, entity: &Entity, value: Option<T>) {
+    if let (Some(value), Some(mut component)) = (value, scene.get_component_mut::<T>(entity)) {
+        if *component != value {
+            *component = value;
+        }
+    }
+}
+
+pub fn update_scripted_behaviours(scene: &Scene, _ticks: SystemTicks) {
+    let resources = (scene.resource::<ScriptingEngine>(), scene.resource::<Time>(), scene.resource::<Input>());
+    let (scripting_engine, time, input) = match resources {
+        (Some(scripting_engine), Some(time), Some(input)) => (scripting_engine, time, input),
+        _ => {
+            warn!("Couldn't find the scripting resources");
+            return;
+        }
+    };
+
+    let query = match scene.query::<(Entity, &mut ScriptedBehaviour, Option<&ScriptEnabled>)>() {
+        Ok(query) => query,
+        Err(e) => {
+            warn!("Couldn't query scripted behaviours: {}", e);
+            return;
+        }
+    };
+
+    // Each file is checked once per frame, however many behaviours run it
+    let mut modified_times: HashMap<String, Result<SystemTime, String>> = HashMap::new();
+    for (entity, mut behaviour, enabled) in query {
+        if enabled.is_some_and(|enabled| !enabled.0) {
+            continue;
+        }
+        let modified = modified_times.entry(behaviour.file_name.clone()).or_insert_with(|| behaviour.get_modified()).clone();
+        behaviour.reload_if_changed(&scripting_engine.engine, modified);
+
+        let script_entity = ScriptEntity {
+            entity,
+            transform: scene.component_storage.get_entity_component::<Transform>(&entity).map(|transform| transform.clone()),
+            mesh: scene.component_storage.get_entity_component::<MeshInstance>(&entity).map(|mesh| mesh.clone()),
+            state: behaviour.get_state().clone()
+        };
+        let mut this = rhai::Dynamic::from(script_entity);
+        behaviour.run(&scripting_engine.engine, &mut this, &time, &input);
+
+        match this.try_cast::<ScriptEntity>() {
+            Some(script_entity) => {
+                write_back(scene, &entity, script_entity.transform);
+                write_back(scene, &entity, script_entity.mesh);
+                behaviour.set_state(script_entity.state);
+            },
+            None => warn!("{} replaced `this`, its changes are ignored", behaviour.file_name)
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use log::{info, warn};
+use probable_spork_ecs::component::{Component, ComponentStorage};
+use rhai::{Engine, AST, Scope, Dynamic, Map, CallFnOptions, FuncArgs};
+
+use crate::{time::Time, input::Input};
+
+const SCRIPT_FOLDER: &str = "/src/assets/scripts/";
+
+// Runs a Rhai script file on its entity. The script can define `fn setup(time, input)` and
+// `fn update(time, input)`, `this` is the entity (see ScriptEntity for what it exposes)
+pub struct ScriptedBehaviour {
+    pub file_name: String,
+    ast: Option<AST>,
+    scope: Scope<'static>,
+    state: Map,
+    modified: Option<SystemTime>,
+    is_setup: bool
+}
+
+impl ScriptedBehaviour {
+    pub fn new(file_name: &str) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            ast: None,
+            scope: Scope::new(),
+            state: Map::new(),
+            modified: None,
+            is_setup: false
+        }
+    }
+
+    fn get_path(&self) -> PathBuf {
+        let mut file_path = String::from(env!("CARGO_MANIFEST_DIR"));
+        file_path.push_str(SCRIPT_FOLDER);
+        file_path.push_str(&self.file_name);
+        PathBuf::from(file_path)
+    }
+
+    pub fn get_state(&self) -> &Map {
+        &self.state
+    }
+
+    pub fn set_state(&mut self, state: Map) {
+        self.state = state;
+    }
+
+    pub fn get_modified(&self) -> Result<SystemTime, String> {
+        fs::metadata(self.get_path()).and_then(|metadata| metadata.modified()).map_err(|e| e.to_string())
+    }
+
+    // Called every frame with the file's modified time. A script that fails to compile keeps running
+    // its last working version, a reload keeps the state and doesn't run setup again
+    pub fn reload_if_changed(&mut self, engine: &Engine, modified: Result<SystemTime, String>) {
+        let modified = match modified {
+            Ok(modified) => modified,
+            Err(e) => {
+                if self.modified.is_none() && self.ast.is_none() {
+                    warn!("Couldn't read script {}: {}", self.file_name, e);
+                    self.modified = Some(SystemTime::UNIX_EPOCH);
+                }
+                return;
+            }
+        };
+        if self.modified == Some(modified) {
+            return;
+        }
+        self.modified = Some(modified);
+
+        match engine.compile_file(self.get_path()) {
+            Ok(ast) => {
+                info!("Loaded script {}", self.file_name);
+                self.ast = Some(ast);
+            },
+            Err(e) => warn!("Couldn't compile script {}: {}", self.file_name, e)
+        }
+    }
+
+    pub fn run(&mut self, engine: &Engine, this: &mut Dynamic, time: &Time, input: &Input) {
+        if !self.is_setup && self.ast.is_some() {
+            self.is_setup = true;
+            self.call(engine, "setup", this, (time.clone(), input.clone()));
+        }
+        self.call(engine, "update", this, (time.clone(), input.clone()));
+    }
+
+    // Functions the script doesn't define are skipped
+    fn call(&mut self, engine: &Engine, name: &str, this: &mut Dynamic, args: impl FuncArgs) {
+        let ast = match &self.ast {
+            Some(ast) => ast,
+            None => return
+        };
+        if !ast.iter_functions().any(|function| function.name == name) {
+            return;
+        }
+
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(this);
+        if let Err(e) = engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, ast, name, args) {
+            warn!("Error in {} of script {}: {}", name, self.file_name, e);
+        }
+    }
+}
+
+impl Component for ScriptedBehaviour {
+    fn setup(&mut self, _world: &ComponentStorage) {
+    }
+    fn update(&mut self, _world: &ComponentStorage) {
+    }
+}
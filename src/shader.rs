@@ -32,6 +32,23 @@ impl Display for ShaderBuilderError {
 
 impl Error for ShaderBuilderError {}
 
+pub fn load_shader_module(device: &wgpu::Device, file_name: &str) -> Result<wgpu::ShaderModule, anyhow::Error> {
+    let project_root = env!("CARGO_MANIFEST_DIR");
+
+    let mut file_path = String::from(project_root);
+    file_path.push_str(SOURCE_FOLDER);
+    file_path.push_str(SHADER_FOLDER);
+    file_path.push_str(file_name);
+    info!("Loading shader: {}", file_path);
+
+    let contents = fs::read_to_string(Path::new(&file_path))?;
+
+    Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(file_name),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(contents))
+    }))
+}
+
 pub struct ShaderUniform {
     pub label: &'static str,
     pub layout: wgpu::BindGroupLayout,
@@ -70,7 +87,7 @@ impl Shader {
             u.buffer.as_ref()
         } else { None };
 
-        return buffer;
+        buffer
     }
 }
 
@@ -84,20 +101,7 @@ impl ShaderBuilder {
     }
 
     pub fn load_shader(mut self, device: &wgpu::Device, file_name: &'static str) -> Result<Self, anyhow::Error> {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-
-        let mut file_path = String::from(project_root);
-        file_path.push_str(SOURCE_FOLDER);
-        file_path.push_str(SHADER_FOLDER);
-        file_path.push_str(file_name);
-        info!("Loading shader: {}", file_path);
-
-        let contents = fs::read_to_string(Path::new(&file_path))?;
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some(file_name),
-            source: wgpu::ShaderSource::Wgsl(Cow::from(contents))
-        });
+        let shader = load_shader_module(device, file_name)?;
 
         self.shader = Some(shader);
         self.label = file_name;
@@ -178,7 +182,7 @@ impl ShaderBuilder {
         self
     }
 
-    pub fn build(self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Result<Shader, ShaderBuilderError> {
+    pub fn build(self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Result<Shader, ShaderBuilderError> {
         let layouts_ref = self.uniforms.iter().map(|layout| &layout.layout).collect::<Vec<_>>();
 
         info!("Building shader: {}, uniforms: {}", self.label, self.uniforms.len());
@@ -225,7 +229,7 @@ impl ShaderBuilder {
                         bias: wgpu::DepthBiasState::default()
                     }),
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: sample_count,
                         mask: !0,
                         alpha_to_coverage_enabled: false
                     },
@@ -241,4 +245,10 @@ impl ShaderBuilder {
             None => Err(ShaderBuilderError::ShaderNotLoaded)
         }
     }
+}
+
+impl Default for ShaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
\ No newline at end of file
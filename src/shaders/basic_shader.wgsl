@@ -13,12 +13,14 @@ struct InstanceInput {
     @location(5) row_0: vec4<f32>,
     @location(6) row_1: vec4<f32>,
     @location(7) row_2: vec4<f32>,
-    @location(8) row_3: vec4<f32>
+    @location(8) row_3: vec4<f32>,
+    @location(9) fade: f32
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) fade: f32
 };
 
 @vertex
@@ -26,7 +28,7 @@ fn vs_main(
     model: VertexInput,
     instance: InstanceInput
 ) -> VertexOutput {
-    let instance = mat4x4<f32>(
+    let model_matrix = mat4x4<f32>(
         instance.row_0,
         instance.row_1,
         instance.row_2,
@@ -34,8 +36,9 @@ fn vs_main(
     );
 
     var out: VertexOutput;
-    out.clip_position = camera.view_proj * instance * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
     out.tex_coords = model.tex_coords;
+    out.fade = instance.fade;
     return out;
 }
 
@@ -44,7 +47,29 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+// Ordered 4x4 Bayer threshold for the pixel, in 0..1
+fn dither_threshold(position: vec2<f32>) -> f32 {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    let index = (u32(position.y) % 4u) * 4u + u32(position.x) % 4u;
+    return (bayer[index] + 0.5) / 16.0;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    // Sampled before the discard, implicit derivatives need uniform control flow
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    let threshold = dither_threshold(in.clip_position.xy);
+    // Negative fade keeps exactly the pixels a positive one of the same size drops
+    let is_faded_out = select(threshold >= in.fade, threshold < -in.fade, in.fade < 0.0);
+    if is_faded_out {
+        discard;
+    }
+
+    return color;
 }
\ No newline at end of file
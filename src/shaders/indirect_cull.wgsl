@@ -0,0 +1,67 @@
+struct CullUniform {
+    planes: array<vec4<f32>, 6>,
+    instance_count: u32
+};
+@group(0) @binding(0)
+var<uniform> cull: CullUniform;
+
+struct Instance {
+    model: mat4x4<f32>,
+    bounding_sphere: vec4<f32>,
+    fade: f32,
+    mesh_index: u32
+};
+@group(0) @binding(1)
+var<storage, read> instances: array<Instance>;
+
+// Where the visible instances of each mesh start in the output buffer
+@group(0) @binding(2)
+var<storage, read> instance_offsets: array<u32>;
+
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32
+};
+@group(0) @binding(3)
+var<storage, read_write> draw_args: array<DrawIndexedIndirect>;
+
+// Same layout as TransformInstanceRaw, 16 floats of the model matrix and the fade
+const INSTANCE_STRIDE: u32 = 17u;
+@group(0) @binding(4)
+var<storage, read_write> visible_instances: array<f32>;
+
+fn is_visible(center: vec3<f32>, radius: f32) -> bool {
+    for (var i = 0u; i < 6u; i = i + 1u) {
+        let plane = cull.planes[i];
+        if dot(plane.xyz, center) + plane.w < -radius {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= cull.instance_count {
+        return;
+    }
+
+    let instance = instances[index];
+    let center = (instance.model * vec4<f32>(instance.bounding_sphere.xyz, 1.0)).xyz;
+    if instance.fade == 0.0 || !is_visible(center, instance.bounding_sphere.w) {
+        return;
+    }
+
+    let slot = atomicAdd(&draw_args[instance.mesh_index].instance_count, 1u);
+    let base = (instance_offsets[instance.mesh_index] + slot) * INSTANCE_STRIDE;
+    for (var column = 0u; column < 4u; column = column + 1u) {
+        for (var row = 0u; row < 4u; row = row + 1u) {
+            visible_instances[base + column * 4u + row] = instances[index].model[column][row];
+        }
+    }
+    visible_instances[base + 16u] = instance.fade;
+}
@@ -72,44 +72,4 @@ impl Texture {
             sampler: diffuse_sampler
         })
     }
-
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, 
-        label: &str) -> Self {
-            let size = wgpu::Extent3d {
-                width: config.width,
-                height: config.height,
-                depth_or_array_layers: 1
-            };
-
-            let desc = wgpu::TextureDescriptor {
-                label: Some(label),
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: Self::DEPTH_FORMAT,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[]
-            };
-
-            let texture = device.create_texture(&desc);
-
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            let sampler = device.create_sampler(
-                &wgpu::SamplerDescriptor {
-                    address_mode_u: wgpu::AddressMode::ClampToEdge,
-                    address_mode_v: wgpu::AddressMode::ClampToEdge,
-                    address_mode_w: wgpu::AddressMode::ClampToEdge,
-                    mag_filter: wgpu::FilterMode::Linear,
-                    min_filter: wgpu::FilterMode::Linear,
-                    mipmap_filter: wgpu::FilterMode::Nearest,
-                    compare: Some(wgpu::CompareFunction::LessEqual),
-                    lod_min_clamp: 0.0,
-                    lod_max_clamp: 100.0,
-                    ..Default::default()
-                }
-            );
-
-            Self {texture, view, sampler}
-    }
 }
\ No newline at end of file
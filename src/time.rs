@@ -0,0 +1,56 @@
+use std::time::Instant;
+
+// Caps the catch-up after a long frame, otherwise slow fixed updates keep falling further behind
+const MAX_FIXED_STEPS: u32 = 5;
+
+// Frame timing, stored as a scene resource and updated once at the start of every frame.
+// Times are in seconds, delta and elapsed follow the time scale while unscaled_delta doesn't
+#[derive(Clone)]
+pub struct Time {
+    pub delta: f32,
+    pub unscaled_delta: f32,
+    pub elapsed: f64,
+    pub frame_count: u64,
+    pub time_scale: f32,
+    pub fixed_delta: f32,
+    // Fixed steps to run this frame, the remainder carries over to the next one
+    pub fixed_steps: u32,
+    fixed_accumulator: f32,
+    last_update: Option<Instant>
+}
+
+impl Time {
+    pub fn new(fixed_delta: f32) -> Self {
+        Self {
+            delta: 0.0,
+            unscaled_delta: 0.0,
+            elapsed: 0.0,
+            frame_count: 0,
+            time_scale: 1.0,
+            fixed_delta,
+            fixed_steps: 0,
+            fixed_accumulator: 0.0,
+            last_update: None
+        }
+    }
+
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        self.unscaled_delta = self.last_update.map_or(0.0, |last_update| (now - last_update).as_secs_f32());
+        self.last_update = Some(now);
+
+        self.delta = self.unscaled_delta * self.time_scale;
+        self.elapsed += self.delta as f64;
+        self.frame_count += 1;
+
+        self.fixed_accumulator += self.delta;
+        self.fixed_steps = 0;
+        while self.fixed_accumulator >= self.fixed_delta && self.fixed_steps < MAX_FIXED_STEPS {
+            self.fixed_accumulator -= self.fixed_delta;
+            self.fixed_steps += 1;
+        }
+        if self.fixed_steps == MAX_FIXED_STEPS {
+            self.fixed_accumulator = 0.0;
+        }
+    }
+}
@@ -0,0 +1,23 @@
+// Kahn's algorithm over dependencies[i], the indices that have to come before i.
+// Picking the first ready index keeps the original order whenever possible.
+// On a cycle, returns the indices that couldn't be ordered
+pub fn topological_sort(dependencies: &[Vec<usize>]) -> Result<Vec<usize>, Vec<usize>> {
+    let mut remaining: Vec<usize> = dependencies.iter().map(|deps| deps.len()).collect();
+    let mut is_sorted = vec![false; dependencies.len()];
+    let mut order = Vec::with_capacity(dependencies.len());
+
+    while let Some(index) = (0..dependencies.len()).find(|i| !is_sorted[*i] && remaining[*i] == 0) {
+        is_sorted[index] = true;
+        order.push(index);
+
+        for (dependent, deps) in dependencies.iter().enumerate() {
+            remaining[dependent] -= deps.iter().filter(|dep| **dep == index).count();
+        }
+    }
+
+    if order.len() != dependencies.len() {
+        return Err((0..dependencies.len()).filter(|index| !is_sorted[*index]).collect());
+    }
+
+    Ok(order)
+}